@@ -0,0 +1,379 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    parser::ast::{ASTNode, BoxedASTNode},
+    passes::resolver::BindingId,
+    scanner::{Token, TokenType},
+    util::{diagnostic::Diagnostic, unified_error, HastyError},
+};
+
+use super::interpreter::{Interpreter, Value};
+
+pub mod vm;
+
+/// Single bytecode instruction. Operands follow the opcode as big endian `u16`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    /// Pushes constant with index given by the operand.
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    /// Pushes value of local in the slot given by the operand.
+    GetLocal,
+    /// Pops value into local in the slot given by the operand.
+    SetLocal,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Negate,
+    Not,
+    /// Jumps forward by the operand if value on top of the stack is falsy, without popping it.
+    JumpIfFalse,
+    /// Jumps forward by the operand if value on top of the stack is truthy, without popping it.
+    JumpIfTrue,
+    /// Stops execution, returning value on top of the stack.
+    Return,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 22] = [
+        OpCode::Constant, OpCode::Nil, OpCode::True, OpCode::False, OpCode::Pop,
+        OpCode::GetLocal, OpCode::SetLocal, OpCode::Add, OpCode::Subtract,
+        OpCode::Multiply, OpCode::Divide, OpCode::Equal, OpCode::NotEqual,
+        OpCode::Less, OpCode::LessEqual, OpCode::Greater, OpCode::GreaterEqual,
+        OpCode::Negate, OpCode::Not, OpCode::JumpIfFalse, OpCode::JumpIfTrue,
+        OpCode::Return,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::ALL.get(byte as usize).copied()
+    }
+
+    /// Number of operand bytes following the opcode.
+    pub fn operand_size(&self) -> usize {
+        match self {
+            OpCode::Constant | OpCode::GetLocal | OpCode::SetLocal
+            | OpCode::JumpIfFalse | OpCode::JumpIfTrue => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// Compiled program.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    /// Source line of every byte in `code`.
+    pub lines: Vec<usize>,
+    pub constants: Vec<Value>,
+    /// Names of locals, indexed by slot.
+    pub locals: Vec<String>,
+}
+
+impl Chunk {
+    fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    fn write_op(&mut self, op: OpCode, line: usize) {
+        self.write(op as u8, line);
+    }
+
+    fn write_u16(&mut self, value: u16, line: usize) {
+        for byte in value.to_be_bytes() {
+            self.write(byte, line);
+        }
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    /// Human readable listing of all instructions.
+    pub fn disassemble(&self) -> String {
+        let mut result = String::new();
+        let mut offset = 0;
+        while offset < self.code.len() {
+            offset = self.disassemble_instruction(offset, &mut result);
+        }
+        result
+    }
+
+    /// Writes single instruction, returning offset of the next one.
+    pub fn disassemble_instruction(&self, offset: usize, result: &mut String) -> usize {
+        let _ = write!(result, "{:04} ", offset);
+        if offset > 0 && self.lines[offset] == self.lines[offset - 1] {
+            let _ = write!(result, "   | ");
+        } else {
+            let _ = write!(result, "{:4} ", self.lines[offset]);
+        }
+
+        let Some(op) = OpCode::from_byte(self.code[offset]) else {
+            let _ = writeln!(result, "UNKNOWN {}", self.code[offset]);
+            return offset + 1;
+        };
+
+        let name = format!("{:?}", op);
+        if op.operand_size() == 0 {
+            let _ = writeln!(result, "{}", name);
+            return offset + 1;
+        }
+
+        let operand = self.read_u16(offset + 1);
+        let _ = match op {
+            OpCode::Constant => writeln!(result, "{:<16} {:4} '{}'", name, operand, self.constants[operand as usize]),
+            OpCode::GetLocal | OpCode::SetLocal => writeln!(result, "{:<16} {:4} '{}'", name, operand, self.locals[operand as usize]),
+            _ => writeln!(result, "{:<16} {:4} -> {}", name, operand, offset + 3 + operand as usize),
+        };
+        offset + 3
+    }
+}
+
+#[derive(Debug)]
+pub enum BytecodeErrorTy {
+    /// Variable has no binding, because resolver did not run.
+    MissingBinding,
+    UnsupportedOperator,
+    UnsupportedLiteral,
+    /// More constants or locals than an operand can address.
+    TooManyValues,
+    /// Jump is longer than an operand can address.
+    JumpTooLong,
+}
+
+#[derive(Debug)]
+pub struct BytecodeError {
+    pub ty: BytecodeErrorTy,
+    pub token: Token,
+}
+
+impl BytecodeError {
+    pub fn new(ty: BytecodeErrorTy, token: Token) -> Self {
+        Self { ty, token }
+    }
+}
+
+impl HastyError for BytecodeError {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("BYTECODE", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match self.ty {
+            BytecodeErrorTy::MissingBinding => format!("Variable '{}' is not resolved.", self.token.lexeme),
+            BytecodeErrorTy::UnsupportedOperator => format!("Operator '{}' is not supported.", self.token.lexeme),
+            BytecodeErrorTy::UnsupportedLiteral => format!("Literal '{}' is not supported.", self.token.lexeme),
+            BytecodeErrorTy::TooManyValues => "Too many constants or variables in one program.".to_string(),
+            BytecodeErrorTy::JumpTooLong => "Too much code to jump over.".to_string(),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        let code = match self.ty {
+            BytecodeErrorTy::MissingBinding => "E0503",
+            BytecodeErrorTy::UnsupportedOperator => "E0501",
+            BytecodeErrorTy::UnsupportedLiteral => "E0502",
+            BytecodeErrorTy::TooManyValues => "E0505",
+            BytecodeErrorTy::JumpTooLong => "E0506",
+        };
+        Diagnostic::error(self.get_error_description()).with_code(code).with_primary(&self.token, "")
+    }
+}
+
+/// Lowers AST into bytecode for the `vm::Vm`.
+/// Locals are assigned slots by their `BindingId`, so resolver has to run first.
+pub struct BytecodeCompiler {
+    chunk: Chunk,
+    slots: HashMap<BindingId, u16>,
+}
+
+impl Default for BytecodeCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BytecodeCompiler {
+    pub fn new() -> Self {
+        Self {
+            chunk: Chunk::default(),
+            slots: HashMap::new(),
+        }
+    }
+
+    /// Compiles whole program. Running it returns value of the last statement,
+    /// same as `Interpreter::interpret`.
+    pub fn compile(mut self, ast: &[BoxedASTNode]) -> Result<Chunk, BytecodeError> {
+        for (i, node) in ast.iter().enumerate() {
+            self.compile_node(node)?;
+
+            let line = node.borrow().position().line;
+            let declaration = matches!(**node.borrow(), ASTNode::VarDecl { .. } | ASTNode::Test { .. });
+            if i + 1 == ast.len() {
+                if declaration { self.chunk.write_op(OpCode::Nil, line); }
+            } else if !declaration {
+                self.chunk.write_op(OpCode::Pop, line);
+            }
+        }
+
+        if ast.is_empty() {
+            self.chunk.write_op(OpCode::Nil, 0);
+        }
+        let line = self.chunk.lines.last().copied().unwrap_or(0);
+        self.chunk.write_op(OpCode::Return, line);
+        Ok(self.chunk)
+    }
+
+    fn compile_node(&mut self, node: &BoxedASTNode) -> Result<(), BytecodeError> {
+        let n = node.borrow();
+        match **n {
+            ASTNode::Binary { ref left, ref operator, ref right } => {
+                self.compile_node(left)?;
+                self.compile_node(right)?;
+                let op = match operator.token_type {
+                    TokenType::PLUS => OpCode::Add,
+                    TokenType::MINUS => OpCode::Subtract,
+                    TokenType::STAR => OpCode::Multiply,
+                    TokenType::SLASH => OpCode::Divide,
+                    TokenType::EQUAL_EQUAL => OpCode::Equal,
+                    TokenType::BANG_EQUAL => OpCode::NotEqual,
+                    TokenType::LESS => OpCode::Less,
+                    TokenType::LESS_EQUAL => OpCode::LessEqual,
+                    TokenType::GREATER => OpCode::Greater,
+                    TokenType::GREATER_EQUAL => OpCode::GreaterEqual,
+                    _ => return Err(BytecodeError::new(BytecodeErrorTy::UnsupportedOperator, operator.clone())),
+                };
+                self.chunk.write_op(op, operator.line);
+            },
+            ASTNode::Logical { ref left, ref operator, ref right } => {
+                self.compile_node(left)?;
+                let jump = match operator.token_type {
+                    TokenType::AND => OpCode::JumpIfFalse,
+                    TokenType::OR => OpCode::JumpIfTrue,
+                    _ => return Err(BytecodeError::new(BytecodeErrorTy::UnsupportedOperator, operator.clone())),
+                };
+
+                // Left operand stays on the stack as the result when jumping.
+                self.chunk.write_op(jump, operator.line);
+                let operand = self.chunk.code.len();
+                self.chunk.write_u16(0, operator.line);
+                self.chunk.write_op(OpCode::Pop, operator.line);
+                self.compile_node(right)?;
+                self.patch_jump(operand, operator)?;
+            },
+            ASTNode::Unary { ref operator, ref right } => {
+                self.compile_node(right)?;
+                let op = match operator.token_type {
+                    TokenType::MINUS => OpCode::Negate,
+                    TokenType::BANG => OpCode::Not,
+                    _ => return Err(BytecodeError::new(BytecodeErrorTy::UnsupportedOperator, operator.clone())),
+                };
+                self.chunk.write_op(op, operator.line);
+            },
+            ASTNode::Literal { ref value } => match value.token_type {
+                TokenType::NIL => self.chunk.write_op(OpCode::Nil, value.line),
+                TokenType::TRUE => self.chunk.write_op(OpCode::True, value.line),
+                TokenType::FALSE => self.chunk.write_op(OpCode::False, value.line),
+                _ => {
+                    let constant = Interpreter::literal(value)
+                        .map_err(|error| BytecodeError::new(BytecodeErrorTy::UnsupportedLiteral, error.token))?;
+                    let index = self.constant(constant, value)?;
+                    self.chunk.write_op(OpCode::Constant, value.line);
+                    self.chunk.write_u16(index, value.line);
+                },
+            },
+            ASTNode::Grouping { ref expr } => self.compile_node(expr)?,
+            ASTNode::VarDecl { ref name, ref initializer, .. } => {
+                match initializer {
+                    Some(initializer) => self.compile_node(initializer)?,
+                    None => self.chunk.write_op(OpCode::Nil, name.line),
+                }
+
+                let id = node.meta.get::<BindingId>()
+                    .ok_or_else(|| BytecodeError::new(BytecodeErrorTy::MissingBinding, name.clone()))?;
+                let slot = u16::try_from(self.chunk.locals.len())
+                    .map_err(|_| BytecodeError::new(BytecodeErrorTy::TooManyValues, name.clone()))?;
+                self.slots.insert(*id, slot);
+                self.chunk.locals.push(name.lexeme.clone());
+
+                self.chunk.write_op(OpCode::SetLocal, name.line);
+                self.chunk.write_u16(slot, name.line);
+            },
+            ASTNode::Variable { ref name } => {
+                let slot = node.meta.get::<BindingId>()
+                    .and_then(|id| self.slots.get(&id).copied())
+                    .ok_or_else(|| BytecodeError::new(BytecodeErrorTy::MissingBinding, name.clone()))?;
+                self.chunk.write_op(OpCode::GetLocal, name.line);
+                self.chunk.write_u16(slot, name.line);
+            },
+            // Tests are only run by the test runner, declaring one does nothing.
+            ASTNode::Test { .. } | ASTNode::Assert { .. } => {},
+        }
+        Ok(())
+    }
+
+    /// Index of a constant in the pool, reusing equal constants.
+    fn constant(&mut self, value: Value, token: &Token) -> Result<u16, BytecodeError> {
+        let index = match self.chunk.constants.iter().position(|constant| *constant == value) {
+            Some(index) => index,
+            None => {
+                self.chunk.constants.push(value);
+                self.chunk.constants.len() - 1
+            },
+        };
+        u16::try_from(index).map_err(|_| BytecodeError::new(BytecodeErrorTy::TooManyValues, token.clone()))
+    }
+
+    /// Points jump operand at `operand` to the end of the code.
+    fn patch_jump(&mut self, operand: usize, token: &Token) -> Result<(), BytecodeError> {
+        let distance = u16::try_from(self.chunk.code.len() - operand - 2)
+            .map_err(|_| BytecodeError::new(BytecodeErrorTy::JumpTooLong, token.clone()))?;
+        self.chunk.code[operand..operand + 2].copy_from_slice(&distance.to_be_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::testing::resolve;
+
+    use super::*;
+
+    #[track_caller]
+    fn compile(code: &str) -> Chunk {
+        BytecodeCompiler::new().compile(&resolve(code)).unwrap()
+    }
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(
+            compile("let x = 1;\nx + 1 || -x").disassemble(),
+            concat!(
+                "0000    0 Constant            0 '1'\n",
+                "0003    | SetLocal            0 'x'\n",
+                "0006    1 GetLocal            0 'x'\n",
+                "0009    | Constant            0 '1'\n",
+                "0012    | Add\n",
+                "0013    | JumpIfTrue          5 -> 21\n",
+                "0016    | Pop\n",
+                "0017    | GetLocal            0 'x'\n",
+                "0020    | Negate\n",
+                "0021    | Return\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use crate::backend::interpreter::Value;
+
+use super::{Chunk, OpCode};
+
+/// Stack based virtual machine executing a `Chunk`.
+/// Semantics are the same as in `Interpreter`.
+pub struct Vm {
+    stack: Vec<Value>,
+    locals: Vec<Value>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            locals: Vec::new(),
+        }
+    }
+
+    /// Runs chunk until `Return`, returning value on top of the stack.
+    /// Chunk is expected to come from `BytecodeCompiler`, malformed bytecode panics.
+    pub fn run(&mut self, chunk: &Chunk) -> Value {
+        self.stack.clear();
+        self.locals = vec![Value::Nil; chunk.locals.len()];
+
+        let mut ip = 0;
+        loop {
+            let op = OpCode::from_byte(chunk.code[ip]).expect("Invalid opcode.");
+            let operand = if op.operand_size() > 0 { chunk.read_u16(ip + 1) as usize } else { 0 };
+            ip += 1 + op.operand_size();
+
+            match op {
+                OpCode::Constant => self.stack.push(chunk.constants[operand].clone()),
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => { self.pop(); },
+                OpCode::GetLocal => self.stack.push(self.locals[operand].clone()),
+                OpCode::SetLocal => self.locals[operand] = self.pop(),
+                OpCode::Add => self.binary(|l, r| l.add(&r)),
+                OpCode::Subtract => self.binary(|l, r| Value::Number(l.to_number() - r.to_number())),
+                OpCode::Multiply => self.binary(|l, r| Value::Number(l.to_number() * r.to_number())),
+                OpCode::Divide => self.binary(|l, r| Value::Number(l.to_number() / r.to_number())),
+                OpCode::Equal => self.binary(|l, r| Value::Bool(l.strict_equals(&r))),
+                OpCode::NotEqual => self.binary(|l, r| Value::Bool(!l.strict_equals(&r))),
+                OpCode::Less => self.binary(|l, r| Value::Bool(l.compare(&r).is_some_and(|o| o.is_lt()))),
+                OpCode::LessEqual => self.binary(|l, r| Value::Bool(l.compare(&r).is_some_and(|o| o.is_le()))),
+                OpCode::Greater => self.binary(|l, r| Value::Bool(l.compare(&r).is_some_and(|o| o.is_gt()))),
+                OpCode::GreaterEqual => self.binary(|l, r| Value::Bool(l.compare(&r).is_some_and(|o| o.is_ge()))),
+                OpCode::Negate => {
+                    let value = self.pop();
+                    self.stack.push(Value::Number(-value.to_number()));
+                },
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                },
+                OpCode::JumpIfFalse => if !self.peek().is_truthy() { ip += operand },
+                OpCode::JumpIfTrue => if self.peek().is_truthy() { ip += operand },
+                OpCode::Return => return self.pop(),
+            }
+        }
+    }
+
+    fn binary(&mut self, operation: impl FnOnce(Value, Value) -> Value) {
+        let right = self.pop();
+        let left = self.pop();
+        self.stack.push(operation(left, right));
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("Stack underflow.")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("Stack underflow.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{bytecode::BytecodeCompiler, interpreter::Interpreter},
+        util::testing::resolve,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_same_as_interpreter() {
+        let programs = [
+            "1 + 2 * 3",
+            "let x = 2; let x = x * 3; x / 4",
+            "\"a\" + 1 + true",
+            "nil || 0 || \"b\"",
+            "1 && nil",
+            "1 == 1.0 && 1 != \"1\" && \"b\" > \"a\" && !(1 < \"x\")",
+            "\"a\" - 1",
+            "let y = 1;",
+            "",
+        ];
+
+        for program in programs {
+            let ast = resolve(program);
+
+            let chunk = BytecodeCompiler::new().compile(&ast).unwrap();
+            assert_eq!(
+                Vm::new().run(&chunk).to_string(),
+                Interpreter::new().interpret(&ast).unwrap().to_string(),
+                "{}", program
+            );
+        }
+    }
+}
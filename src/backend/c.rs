@@ -0,0 +1,367 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Write};
+
+use crate::{
+    parser::ast::{ASTNode, BoxedASTNode},
+    passes::{resolver::BindingId, typecheck::Type},
+    scanner::{Token, TokenType},
+    util::{diagnostic::Diagnostic, unified_error, HastyError},
+};
+
+/// Runtime header included by every generated file, has to be placed next to it.
+pub const RUNTIME_HEADER: &str = include_str!("c/hasty_runtime.h");
+pub const RUNTIME_HEADER_NAME: &str = "hasty_runtime.h";
+
+/// Prefix of every declared name, so that they never collide with C keywords
+/// or declarations of the standard headers included by the runtime.
+const NAME_PREFIX: &str = "h_";
+
+#[derive(Debug)]
+pub enum CErrorTy {
+    /// Node has no type or binding, because type checker or resolver did not run.
+    MissingMetadata,
+    /// Values of this type can not be represented in C.
+    UnsupportedType(Type),
+    UnsupportedOperator,
+    /// Literal does not fit in its C type.
+    UnsupportedLiteral,
+}
+
+#[derive(Debug)]
+pub struct CError {
+    pub ty: CErrorTy,
+    pub token: Token,
+}
+
+impl CError {
+    pub fn new(ty: CErrorTy, token: Token) -> Self {
+        Self { ty, token }
+    }
+}
+
+impl HastyError for CError {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("C", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match self.ty {
+            CErrorTy::MissingMetadata => format!("'{}' has no type, was the program type checked?", self.token.lexeme),
+            CErrorTy::UnsupportedType(ty) => format!("Type {} is not supported in C.", ty),
+            CErrorTy::UnsupportedOperator => format!("Operator '{}' is not supported.", self.token.lexeme),
+            CErrorTy::UnsupportedLiteral => format!("Literal '{}' is not supported.", self.token.lexeme),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        let code = match self.ty {
+            CErrorTy::MissingMetadata => "E0503",
+            CErrorTy::UnsupportedType(_) => "E0504",
+            CErrorTy::UnsupportedOperator => "E0501",
+            CErrorTy::UnsupportedLiteral => "E0502",
+        };
+        Diagnostic::error(self.get_error_description()).with_code(code).with_primary(&self.token, "")
+    }
+}
+
+/// Compiles typed AST to C99.
+/// Declarations become global variables prefixed with `h_`, public ones with external linkage,
+/// and all statements are executed by `void hasty_init(void)`.
+/// Requires `BindingId` and `Type` metadata, so resolver and type checker have to run first.
+pub struct CCompiler {
+    emit_main: bool,
+    names: HashMap<BindingId, String>,
+    name_counts: HashMap<String, usize>,
+    /// C types of globals holding left operands of logical operators.
+    temporaries: RefCell<Vec<&'static str>>,
+}
+
+impl Default for CCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CCompiler {
+    pub fn new() -> Self {
+        Self {
+            emit_main: false,
+            names: HashMap::new(),
+            name_counts: HashMap::new(),
+            temporaries: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Also emits `main` calling `hasty_init`, so the result can be built as a program.
+    pub fn with_main(mut self) -> Self {
+        self.emit_main = true;
+        self
+    }
+
+    pub fn compile(mut self, ast: &[BoxedASTNode]) -> Result<String, CError> {
+        let mut globals = String::new();
+        let mut init = String::new();
+
+        for node in ast.iter().filter(|node| !node.borrow().is_test()) {
+            let n = node.borrow();
+            if let ASTNode::VarDecl { ref name, ref initializer, public, .. } = **n {
+                let ty = Self::c_type(node)?;
+                let value = match initializer {
+                    Some(initializer) => self.operand(initializer, node)?,
+                    None => Self::zero(ty).to_string(),
+                };
+
+                let id = node.meta.get::<BindingId>()
+                    .ok_or_else(|| CError::new(CErrorTy::MissingMetadata, name.clone()))?;
+                let c_name = self.declare(&name.lexeme);
+                self.names.insert(*id, c_name.clone());
+
+                let _ = writeln!(globals, "{}{} {};", if public { "" } else { "static " }, ty, c_name);
+                let _ = writeln!(init, "    {} = {};", c_name, value);
+            } else {
+                let _ = writeln!(init, "    (void){};", self.expression(node)?);
+            }
+        }
+
+        for (i, ty) in self.temporaries.borrow().iter().enumerate() {
+            let _ = writeln!(globals, "static {} hasty_tmp_{};", ty, i);
+        }
+
+        let mut result = format!("#include \"{}\"\n\n", RUNTIME_HEADER_NAME);
+        if !globals.is_empty() {
+            result.push_str(&globals);
+            result.push('\n');
+        }
+        let _ = write!(result, "void hasty_init(void) {{\n{}}}\n", init);
+        if self.emit_main {
+            result.push_str("\nint main(void) {\n    hasty_init();\n    return 0;\n}\n");
+        }
+        Ok(result)
+    }
+
+    fn expression(&self, node: &BoxedASTNode) -> Result<String, CError> {
+        let n = node.borrow();
+        let ty = Self::node_type(node)?;
+        Ok(match **n {
+            ASTNode::Binary { ref left, ref operator, ref right } => {
+                let (lt, rt) = (Self::node_type(left)?, Self::node_type(right)?);
+                let (l, r) = (self.expression(left)?, self.expression(right)?);
+                let numeric = lt.is_numeric() && rt.is_numeric();
+
+                match operator.token_type {
+                    TokenType::EQUAL_EQUAL | TokenType::BANG_EQUAL => {
+                        let negate = if operator.token_type == TokenType::BANG_EQUAL { "!" } else { "" };
+                        match (lt, rt) {
+                            (Type::String, Type::String) => format!("{}hasty_string_eq({}, {})", negate, l, r),
+                            _ if numeric && lt != rt => format!("({} {} {})", Self::to_double(&l, lt), operator.lexeme, Self::to_double(&r, rt)),
+                            _ if lt == rt => format!("({} {} {})", l, operator.lexeme, r),
+                            // Values of different types are never equal, same as `===`.
+                            _ => (operator.token_type == TokenType::BANG_EQUAL).to_string(),
+                        }
+                    },
+                    TokenType::LESS | TokenType::LESS_EQUAL | TokenType::GREATER | TokenType::GREATER_EQUAL => {
+                        if lt == Type::String {
+                            format!("(hasty_string_cmp({}, {}) {} 0)", l, r, operator.lexeme)
+                        } else if lt != rt {
+                            format!("({} {} {})", Self::to_double(&l, lt), operator.lexeme, Self::to_double(&r, rt))
+                        } else {
+                            format!("({} {} {})", l, operator.lexeme, r)
+                        }
+                    },
+                    TokenType::PLUS if ty == Type::String => format!("hasty_string_concat({}, {})", l, r),
+                    TokenType::PLUS | TokenType::MINUS | TokenType::STAR if ty == Type::Int => {
+                        let function = match operator.token_type {
+                            TokenType::PLUS => "hasty_add_i64",
+                            TokenType::MINUS => "hasty_sub_i64",
+                            _ => "hasty_mul_i64",
+                        };
+                        format!("{}({}, {})", function, l, r)
+                    },
+                    TokenType::PLUS | TokenType::MINUS | TokenType::STAR | TokenType::SLASH => {
+                        format!("({} {} {})", Self::to_double(&l, lt), operator.lexeme, Self::to_double(&r, rt))
+                    },
+                    _ => return Err(CError::new(CErrorTy::UnsupportedOperator, operator.clone())),
+                }
+            },
+            ASTNode::Logical { ref left, ref operator, ref right } => {
+                let (l, r) = (self.operand(left, node)?, self.operand(right, node)?);
+                let c_operator = match operator.token_type {
+                    TokenType::AND => "&&",
+                    TokenType::OR => "||",
+                    _ => return Err(CError::new(CErrorTy::UnsupportedOperator, operator.clone())),
+                };
+                if ty == Type::Bool {
+                    return Ok(format!("({} {} {})", l, c_operator, r));
+                }
+
+                // Result is one of the operands, so left one is kept in a temporary global.
+                let truthy = match ty {
+                    Type::Int => "hasty_truthy_i64",
+                    Type::Float => "hasty_truthy_f64",
+                    _ => "hasty_truthy_string",
+                };
+                let temporary = {
+                    let mut temporaries = self.temporaries.borrow_mut();
+                    temporaries.push(Self::c_type(node)?);
+                    format!("hasty_tmp_{}", temporaries.len() - 1)
+                };
+                match operator.token_type {
+                    TokenType::AND => format!("(({} = {}), {}({}) ? {} : {})", temporary, l, truthy, temporary, r, temporary),
+                    _ => format!("(({} = {}), {}({}) ? {} : {})", temporary, l, truthy, temporary, temporary, r),
+                }
+            },
+            ASTNode::Unary { ref operator, ref right } => {
+                let operand_type = Self::node_type(right)?;
+                let operand = self.expression(right)?;
+                match (&operator.token_type, operand_type) {
+                    (TokenType::MINUS, Type::Int) => format!("hasty_neg_i64({})", operand),
+                    (TokenType::MINUS, Type::Float) => format!("(-{})", operand),
+                    (TokenType::BANG, Type::Bool) => format!("(!{})", operand),
+                    (TokenType::BANG, Type::Int) => format!("(!hasty_truthy_i64({}))", operand),
+                    (TokenType::BANG, Type::Float) => format!("(!hasty_truthy_f64({}))", operand),
+                    (TokenType::BANG, Type::String) => format!("(!hasty_truthy_string({}))", operand),
+                    _ => return Err(CError::new(CErrorTy::UnsupportedOperator, operator.clone())),
+                }
+            },
+            ASTNode::Literal { ref value } => match value.token_type {
+                TokenType::INTEGER => match value.lexeme.parse::<i64>() {
+                    Ok(_) => format!("INT64_C({})", value.lexeme),
+                    Err(_) => return Err(CError::new(CErrorTy::UnsupportedLiteral, value.clone())),
+                },
+                TokenType::FLOATING => format!("{:?}", value.lexeme.parse::<f64>().unwrap_or_default()),
+                TokenType::STRING => {
+                    let data = value.data.as_ref()
+                        .and_then(|data| data.downcast_ref::<String>())
+                        .cloned()
+                        .unwrap_or_default();
+                    format!("hasty_string_from({}, {})", Self::string_literal(&data), data.len())
+                },
+                TokenType::TRUE => "true".to_string(),
+                _ => "false".to_string(),
+            },
+            ASTNode::Grouping { ref expr } => self.expression(expr)?,
+            ASTNode::Variable { ref name } => node.meta.get::<BindingId>()
+                .and_then(|id| self.names.get(&id).cloned())
+                .ok_or_else(|| CError::new(CErrorTy::MissingMetadata, name.clone()))?,
+            ASTNode::VarDecl { ref name, .. } => return Err(CError::new(CErrorTy::UnsupportedOperator, name.clone())),
+            ASTNode::Test { .. } | ASTNode::Assert { .. } => return Err(CError::new(CErrorTy::UnsupportedOperator, n.position())),
+        })
+    }
+
+    /// Compiles node whose value is used as `parent`, converting integers to floats when needed.
+    fn operand(&self, node: &BoxedASTNode, parent: &BoxedASTNode) -> Result<String, CError> {
+        let code = self.expression(node)?;
+        Ok(match Self::node_type(parent)? {
+            Type::Float => Self::to_double(&code, Self::node_type(node)?),
+            _ => code,
+        })
+    }
+
+    fn to_double(code: &str, ty: Type) -> String {
+        match ty {
+            Type::Int => format!("(double){}", code),
+            _ => code.to_string(),
+        }
+    }
+
+    /// Type of an already type checked node. `nil` has no representation in C.
+    fn node_type(node: &BoxedASTNode) -> Result<Type, CError> {
+        let ty = node.meta.get::<Type>()
+            .ok_or_else(|| CError::new(CErrorTy::MissingMetadata, node.borrow().position()))?;
+        if *ty == Type::Nil {
+            return Err(CError::new(CErrorTy::UnsupportedType(Type::Nil), node.borrow().position()));
+        }
+        Ok(*ty)
+    }
+
+    /// C type of a node.
+    fn c_type(node: &BoxedASTNode) -> Result<&'static str, CError> {
+        Ok(match Self::node_type(node)? {
+            Type::Int => "int64_t",
+            Type::Float => "double",
+            Type::String => "hasty_string",
+            _ => "bool",
+        })
+    }
+
+    fn zero(c_type: &str) -> &'static str {
+        match c_type {
+            "int64_t" => "0",
+            "double" => "0.0",
+            "hasty_string" => "hasty_string_from(\"\", 0)",
+            _ => "false",
+        }
+    }
+
+    /// Escapes string for a C string literal. Non-ascii bytes are written as octal escapes.
+    fn string_literal(value: &str) -> String {
+        let mut result = String::from("\"");
+        for byte in value.bytes() {
+            match byte {
+                b'"' => result.push_str("\\\""),
+                b'\\' => result.push_str("\\\\"),
+                b'\n' => result.push_str("\\n"),
+                b'\t' => result.push_str("\\t"),
+                b'\r' => result.push_str("\\r"),
+                // `?` could start a trigraph.
+                b' '..=b'~' if byte != b'?' => result.push(byte as char),
+                _ => { let _ = write!(result, "\\{:03o}", byte); },
+            }
+        }
+        result.push('"');
+        result
+    }
+
+    /// Unique C name for a declaration, `h_x`, `h_x__1`, ... for shadowed ones.
+    fn declare(&mut self, name: &str) -> String {
+        let name = format!("{}{}", NAME_PREFIX, name);
+        let count = self.name_counts.entry(name.clone()).or_default();
+        *count += 1;
+        match *count {
+            1 => name,
+            n => format!("{}__{}", name, n - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::testing::typecheck;
+
+    use super::*;
+
+    #[track_caller]
+    fn compile(code: &str) -> Result<String, CError> {
+        CCompiler::new().with_main().compile(&typecheck(code))
+    }
+
+    #[test]
+    fn test_compile_c() {
+        assert_eq!(
+            compile("pub let x = 1; let int = x / 2 + 1; let s = \"a\\\" + \"?\"; let x = -x || 2; s < \"b\" && !int").unwrap(),
+            concat!(
+                "#include \"hasty_runtime.h\"\n\n",
+                "int64_t h_x;\n",
+                "static double h_int;\n",
+                "static hasty_string h_s;\n",
+                "static int64_t h_x__1;\n",
+                "static int64_t hasty_tmp_0;\n\n",
+                "void hasty_init(void) {\n",
+                "    h_x = INT64_C(1);\n",
+                "    h_int = (((double)h_x / (double)INT64_C(2)) + (double)INT64_C(1));\n",
+                "    h_s = hasty_string_concat(hasty_string_from(\"a\\\\\", 2), hasty_string_from(\"\\077\", 1));\n",
+                "    h_x__1 = ((hasty_tmp_0 = hasty_neg_i64(h_x)), hasty_truthy_i64(hasty_tmp_0) ? hasty_tmp_0 : INT64_C(2));\n",
+                "    (void)((hasty_string_cmp(h_s, hasty_string_from(\"b\", 1)) < 0) && (!hasty_truthy_f64(h_int)));\n",
+                "}\n\n",
+                "int main(void) {\n    hasty_init();\n    return 0;\n}\n",
+            )
+        );
+
+        let error = compile("let x;").unwrap_err();
+        assert!(matches!(error.ty, CErrorTy::UnsupportedType(Type::Nil)));
+        let error = compile("9223372036854775808").unwrap_err();
+        assert!(matches!(error.ty, CErrorTy::UnsupportedLiteral));
+    }
+}
@@ -0,0 +1,65 @@
+/* Runtime support for C code generated by hasty. */
+#ifndef HASTY_RUNTIME_H
+#define HASTY_RUNTIME_H
+
+#include <math.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* Immutable string. Strings created by concatenation are never freed. */
+typedef struct {
+    const char *data;
+    size_t length;
+} hasty_string;
+
+static inline hasty_string hasty_string_from(const char *data, size_t length) {
+    hasty_string result = { data, length };
+    return result;
+}
+
+static inline hasty_string hasty_string_concat(hasty_string left, hasty_string right) {
+    char *data = malloc(left.length + right.length + 1);
+    if (data == NULL) abort();
+    memcpy(data, left.data, left.length);
+    memcpy(data + left.length, right.data, right.length);
+    data[left.length + right.length] = '\0';
+    return hasty_string_from(data, left.length + right.length);
+}
+
+static inline int hasty_string_cmp(hasty_string left, hasty_string right) {
+    size_t length = left.length < right.length ? left.length : right.length;
+    int result = memcmp(left.data, right.data, length);
+    if (result != 0) return result;
+    return (left.length > right.length) - (left.length < right.length);
+}
+
+static inline bool hasty_string_eq(hasty_string left, hasty_string right) {
+    return hasty_string_cmp(left, right) == 0;
+}
+
+/* Integer arithmetic wraps around instead of being undefined on overflow. */
+static inline int64_t hasty_add_i64(int64_t left, int64_t right) {
+    return (int64_t)((uint64_t)left + (uint64_t)right);
+}
+
+static inline int64_t hasty_sub_i64(int64_t left, int64_t right) {
+    return (int64_t)((uint64_t)left - (uint64_t)right);
+}
+
+static inline int64_t hasty_mul_i64(int64_t left, int64_t right) {
+    return (int64_t)((uint64_t)left * (uint64_t)right);
+}
+
+static inline int64_t hasty_neg_i64(int64_t value) {
+    return (int64_t)(0 - (uint64_t)value);
+}
+
+/* Truthiness, same as in javascript. */
+static inline bool hasty_truthy_i64(int64_t value) { return value != 0; }
+static inline bool hasty_truthy_f64(double value) { return fabs(value) > 0.0; }
+static inline bool hasty_truthy_string(hasty_string value) { return value.length > 0; }
+
+#endif
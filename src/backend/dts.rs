@@ -0,0 +1,142 @@
+use crate::{
+    compiler::Compiler,
+    parser::ast::{ASTNode, BoxedASTNode},
+    passes::typecheck::Type,
+    scanner::Token,
+    util::{diagnostic::Diagnostic, unified_error, HastyError},
+};
+
+#[derive(Debug)]
+pub enum DtsErrorTy {
+    /// Declaration has no type, because type checker did not run.
+    MissingType,
+}
+
+#[derive(Debug)]
+pub struct DtsError {
+    pub ty: DtsErrorTy,
+    pub token: Token,
+}
+
+impl HastyError for DtsError {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("DTS", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match self.ty {
+            DtsErrorTy::MissingType => format!("Type of '{}' is not known, was the program type checked?", self.token.lexeme),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.get_error_description()).with_code("E0503").with_primary(&self.token, "")
+    }
+}
+
+/// Emits typescript declarations (`.d.ts`) for everything exported from a module.
+/// Requires `Type` metadata, so the type checker has to run first.
+///
+/// Public variables and constants are the only items that can be exported, as the language
+/// has no functions, structs or enums yet. Every other node is matched explicitly in `declaration`,
+/// so that new item kinds can not be skipped silently.
+pub struct DtsEmitter {
+}
+
+impl Default for DtsEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DtsEmitter {
+    pub fn new() -> Self {
+        Self { }
+    }
+
+    pub fn emit(&mut self, ast: &[BoxedASTNode], target: &mut impl std::io::Write) -> Result<(), DtsError> {
+        for (i, node) in ast.iter().enumerate() {
+            // Names declared more than once are exported by their last declaration, same as in javascript.
+            let name = Self::public_name(node);
+            if name.is_some() && ast[i + 1..].iter().any(|later| Self::public_name(later) == name) {
+                continue;
+            }
+            if let Some(declaration) = Self::declaration(node)? {
+                let _ = writeln!(target, "{}", declaration);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Declaration of a single top-level statement, if it is exported.
+    fn declaration(node: &BoxedASTNode) -> Result<Option<String>, DtsError> {
+        let n = node.borrow();
+        let (name, constant) = match **n {
+            ASTNode::VarDecl { ref name, constant, public: true, .. } => (name, constant),
+            ASTNode::VarDecl { public: false, .. } | ASTNode::Test { .. } | ASTNode::Assert { .. }
+            | ASTNode::Binary { .. } | ASTNode::Logical { .. } | ASTNode::Unary { .. }
+            | ASTNode::Literal { .. } | ASTNode::Grouping { .. } | ASTNode::Variable { .. } => return Ok(None),
+        };
+
+        let Some(ty) = node.meta.get::<Type>() else {
+            return Err(DtsError { ty: DtsErrorTy::MissingType, token: name.clone() });
+        };
+
+        let keyword = if constant { "const" } else { "let" };
+        let escaped = Compiler::escape_name(&name.lexeme);
+        let ty = Self::typescript_type(*ty);
+        Ok(Some(if escaped == name.lexeme {
+            format!("export declare {} {}: {};", keyword, escaped, ty)
+        } else {
+            // Reserved words can still be used as export names.
+            format!("declare {} {}: {};\nexport {{ {} as {} }};", keyword, escaped, ty, escaped, name.lexeme)
+        }))
+    }
+
+    fn public_name(node: &BoxedASTNode) -> Option<String> {
+        match **node.borrow() {
+            ASTNode::VarDecl { ref name, public: true, .. } => Some(name.lexeme.clone()),
+            _ => None,
+        }
+    }
+
+    /// Maps hasty type to typescript type.
+    pub fn typescript_type(ty: Type) -> &'static str {
+        match ty {
+            Type::Int | Type::Float => "number",
+            Type::String => "string",
+            Type::Bool => "boolean",
+            Type::Nil => "null",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::testing::typecheck;
+
+    use super::*;
+
+    #[test]
+    fn test_emit_declarations() {
+        let code = concat!(
+            "pub const answer = 42; let hidden = true; pub let name: string = \"a\"; pub let ratio = answer / 5; ",
+            "pub let name = 1; pub let new = 2; answer + 1 test fn works() { assert(hidden) }",
+        );
+        let mut result = Vec::new();
+        DtsEmitter::new().emit(&typecheck(code), &mut result).unwrap();
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            concat!(
+                "export declare const answer: number;\n",
+                "export declare let ratio: number;\n",
+                "export declare let name: number;\n",
+                "declare let new$: number;\nexport { new$ as new };\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use crate::{
+    parser::ast::{ASTNode, BoxedASTNode},
+    scanner::{Token, TokenType},
+    util::{diagnostic::{Diagnostic, Span}, scope::Scope, unified_error, HastyError},
+};
+
+/// Runtime value. Follows javascript, so that programs behave the same
+/// when interpreted and when compiled, e.g. all numbers are floats.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Nil,
+}
+
+impl Value {
+    /// Truthiness of a value, same as in javascript.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Number(value) => *value != 0.0 && !value.is_nan(),
+            Value::String(value) => !value.is_empty(),
+            Value::Bool(value) => *value,
+            Value::Nil => false,
+        }
+    }
+
+    /// Converts value to number, like javascript `Number(value)`.
+    pub fn to_number(&self) -> f64 {
+        match self {
+            Value::Number(value) => *value,
+            Value::String(value) => {
+                let value = value.trim();
+                match value {
+                    "" => 0.0,
+                    "Infinity" | "+Infinity" => f64::INFINITY,
+                    "-Infinity" => f64::NEG_INFINITY,
+                    // Rust accepts things like `inf` or `nan`, javascript does not.
+                    _ if value.chars().all(|c| c.is_ascii_digit() || ".eE+-".contains(c)) => {
+                        value.parse().unwrap_or(f64::NAN)
+                    },
+                    _ => f64::NAN,
+                }
+            },
+            Value::Bool(value) => if *value { 1.0 } else { 0.0 },
+            Value::Nil => 0.0,
+        }
+    }
+
+    /// Addition, concatenating strings if either side is a string.
+    pub fn add(&self, other: &Value) -> Value {
+        match (self, other) {
+            (Value::String(_), _) | (_, Value::String(_)) => Value::String(format!("{}{}", self, other)),
+            _ => Value::Number(self.to_number() + other.to_number()),
+        }
+    }
+
+    /// Ordering used by comparison operators, `None` if either side is NaN.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+            _ => self.to_number().partial_cmp(&other.to_number()),
+        }
+    }
+
+    /// Strict equality, same as javascript `===`.
+    pub fn strict_equals(&self, other: &Value) -> bool {
+        // Derived `PartialEq` already compares floats like javascript, NaN included.
+        self == other
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(value) if value.is_nan() => write!(f, "NaN"),
+            Value::Number(value) if value.is_infinite() => {
+                write!(f, "{}Infinity", if *value < 0.0 { "-" } else { "" })
+            },
+            // Rust prints `-0` for negative zero, javascript does not.
+            Value::Number(value) if *value == 0.0 => write!(f, "0"),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Nil => write!(f, "null"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RuntimeErrorTy {
+    /// Variable was not declared before it was read.
+    UndefinedVariable,
+    /// Operator is not supported by the interpreter.
+    UnsupportedOperator,
+    /// Literal is not supported by the interpreter.
+    UnsupportedLiteral,
+    /// Condition of `assert` in a test was falsy.
+    AssertionFailed {
+        condition: Span,
+        message: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub ty: RuntimeErrorTy,
+    pub token: Token,
+}
+
+impl RuntimeError {
+    pub fn new(ty: RuntimeErrorTy, token: Token) -> Self {
+        Self { ty, token }
+    }
+}
+
+impl HastyError for RuntimeError {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("RUNTIME", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match self.ty {
+            RuntimeErrorTy::UndefinedVariable => format!("Variable '{}' is not defined.", self.token.lexeme),
+            RuntimeErrorTy::UnsupportedOperator => format!("Operator '{}' is not supported.", self.token.lexeme),
+            RuntimeErrorTy::UnsupportedLiteral => format!("Literal '{}' is not supported.", self.token.lexeme),
+            RuntimeErrorTy::AssertionFailed { message: Some(ref message), .. } => format!("Assertion failed: {}", message),
+            RuntimeErrorTy::AssertionFailed { message: None, .. } => "Assertion failed.".to_string(),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        let code = match self.ty {
+            RuntimeErrorTy::UndefinedVariable => "E0601",
+            RuntimeErrorTy::UnsupportedOperator => "E0602",
+            RuntimeErrorTy::UnsupportedLiteral => "E0603",
+            RuntimeErrorTy::AssertionFailed { condition, .. } => {
+                return Diagnostic::error(self.get_error_description()).with_code("E0604")
+                    .with_primary(condition, "condition is falsy");
+            },
+        };
+        Diagnostic::error(self.get_error_description()).with_code(code).with_primary(&self.token, "")
+    }
+}
+
+/// Tree-walking interpreter, evaluating AST directly.
+/// Globals are kept between runs, so it can execute a program piece by piece.
+pub struct Interpreter {
+    globals: Scope<Value>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self {
+            globals: Scope::new(),
+        }
+    }
+
+    /// Interpreter storing variables in `globals`, for example a child scope
+    /// whose variables are only committed if the program succeeds.
+    pub fn with_globals(globals: Scope<Value>) -> Self {
+        Self { globals }
+    }
+
+    /// Runs all statements, returning value of the last one.
+    /// Declarations evaluate to `nil`.
+    pub fn interpret(&mut self, ast: &[BoxedASTNode]) -> Result<Value, RuntimeError> {
+        let mut result = Value::Nil;
+        for node in ast {
+            result = self.evaluate(node)?;
+        }
+        Ok(result)
+    }
+
+    /// Runs body of a test in its own scope. The module containing it has to be interpreted first.
+    pub fn run_test(&mut self, test: &BoxedASTNode) -> Result<(), RuntimeError> {
+        let ASTNode::Test { ref body, .. } = **test.borrow() else { return Ok(()) };
+
+        let module = self.globals.clone();
+        self.globals = module.child();
+        let result = self.interpret(body);
+        self.globals = module;
+        result.map(|_| ())
+    }
+
+    /// Value of a global variable.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.globals.get(name)
+    }
+
+    fn evaluate(&mut self, node: &BoxedASTNode) -> Result<Value, RuntimeError> {
+        let n = node.borrow();
+        Ok(match **n {
+            ASTNode::Binary { ref left, ref operator, ref right } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                Self::binary(left, operator, right)?
+            },
+            ASTNode::Logical { ref left, ref operator, ref right } => {
+                let left = self.evaluate(left)?;
+                // Returns one of the operands, not a bool, same as javascript.
+                match (&operator.token_type, left.is_truthy()) {
+                    (TokenType::AND, false) | (TokenType::OR, true) => left,
+                    (TokenType::AND | TokenType::OR, _) => self.evaluate(right)?,
+                    _ => return Err(RuntimeError::new(RuntimeErrorTy::UnsupportedOperator, operator.clone())),
+                }
+            },
+            ASTNode::Unary { ref operator, ref right } => {
+                let right = self.evaluate(right)?;
+                match operator.token_type {
+                    TokenType::MINUS => Value::Number(-right.to_number()),
+                    TokenType::BANG => Value::Bool(!right.is_truthy()),
+                    _ => return Err(RuntimeError::new(RuntimeErrorTy::UnsupportedOperator, operator.clone())),
+                }
+            },
+            ASTNode::Literal { ref value } => Self::literal(value)?,
+            ASTNode::Grouping { ref expr } => self.evaluate(expr)?,
+            ASTNode::VarDecl { ref name, ref initializer, .. } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Nil,
+                };
+                // Inserting shadows previous declaration with the same name.
+                self.globals.insert(&name.lexeme, value);
+                Value::Nil
+            },
+            ASTNode::Variable { ref name } => self.globals.get(&name.lexeme)
+                .ok_or_else(|| RuntimeError::new(RuntimeErrorTy::UndefinedVariable, name.clone()))?,
+            // Tests are only run by `run_test`.
+            ASTNode::Test { .. } => Value::Nil,
+            ASTNode::Assert { ref keyword, ref condition, ref message } => {
+                if !self.evaluate(condition)?.is_truthy() {
+                    let message = match message {
+                        Some(message) => Some(self.evaluate(message)?.to_string()),
+                        None => None,
+                    };
+                    let condition = condition.borrow().span();
+                    return Err(RuntimeError::new(RuntimeErrorTy::AssertionFailed { condition, message }, keyword.clone()));
+                }
+                Value::Nil
+            },
+        })
+    }
+
+    fn binary(left: Value, operator: &Token, right: Value) -> Result<Value, RuntimeError> {
+        Ok(match operator.token_type {
+            TokenType::PLUS => left.add(&right),
+            TokenType::MINUS => Value::Number(left.to_number() - right.to_number()),
+            TokenType::STAR => Value::Number(left.to_number() * right.to_number()),
+            TokenType::SLASH => Value::Number(left.to_number() / right.to_number()),
+            TokenType::EQUAL_EQUAL => Value::Bool(left.strict_equals(&right)),
+            TokenType::BANG_EQUAL => Value::Bool(!left.strict_equals(&right)),
+            TokenType::LESS | TokenType::LESS_EQUAL | TokenType::GREATER | TokenType::GREATER_EQUAL => {
+                // Every comparison with NaN is false.
+                let Some(ordering) = left.compare(&right) else { return Ok(Value::Bool(false)) };
+                Value::Bool(match operator.token_type {
+                    TokenType::LESS => ordering.is_lt(),
+                    TokenType::LESS_EQUAL => ordering.is_le(),
+                    TokenType::GREATER => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                })
+            },
+            _ => return Err(RuntimeError::new(RuntimeErrorTy::UnsupportedOperator, operator.clone())),
+        })
+    }
+
+    pub(crate) fn literal(value: &Token) -> Result<Value, RuntimeError> {
+        let unsupported = || RuntimeError::new(RuntimeErrorTy::UnsupportedLiteral, value.clone());
+        Ok(match value.token_type {
+            TokenType::INTEGER | TokenType::FLOATING => Value::Number(value.lexeme.parse().map_err(|_| unsupported())?),
+            TokenType::STRING => Value::String(
+                value.data.as_ref().and_then(|data| data.downcast_ref::<String>()).ok_or_else(unsupported)?.clone()
+            ),
+            TokenType::TRUE => Value::Bool(true),
+            TokenType::FALSE => Value::Bool(false),
+            TokenType::NIL => Value::Nil,
+            _ => return Err(unsupported()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::testing::parse;
+
+    use super::*;
+
+    #[track_caller]
+    fn run(code: &str) -> Result<Value, RuntimeError> {
+        Interpreter::new().interpret(&parse(code))
+    }
+
+    #[test]
+    fn test_interpret() {
+        assert_eq!(run("1 + 2 * 3").unwrap(), Value::Number(7.0));
+        assert_eq!(run("let x = 2; let x = x * 3; x / 4").unwrap(), Value::Number(1.5));
+        assert_eq!(run("\"a\" + 1 + true").unwrap(), Value::String("a1true".to_string()));
+        assert_eq!(run("nil || 0 || \"b\"").unwrap(), Value::String("b".to_string()));
+        assert_eq!(run("1 && nil").unwrap(), Value::Nil);
+        assert_eq!(run("1 == 1.0 && 1 != \"1\" && \"b\" > \"a\" && !(1 < \"x\")").unwrap(), Value::Bool(true));
+        assert_eq!(run("1 / 0").unwrap().to_string(), "Infinity");
+        assert_eq!(run("\"a\" - 1").unwrap().to_string(), "NaN");
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let error = run("let x = 1; x + y").unwrap_err();
+        assert!(matches!(error.ty, RuntimeErrorTy::UndefinedVariable));
+        assert_eq!((error.token.lexeme.as_str(), error.token.start), ("y", 15));
+    }
+}
@@ -0,0 +1,7 @@
+//! Additional backends, next to the javascript `compiler`.
+
+pub mod bytecode;
+pub mod c;
+pub mod dts;
+pub mod interpreter;
+pub mod wasm;
@@ -0,0 +1,503 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    parser::ast::{ASTNode, BoxedASTNode},
+    passes::{resolver::BindingId, typecheck::Type},
+    scanner::{Token, TokenType},
+    util::{diagnostic::Diagnostic, unified_error, HastyError},
+};
+
+mod binary;
+
+/// Value type of a webassembly module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValType {
+    I32,
+    I64,
+    F64,
+}
+
+impl ValType {
+    /// Representation of a hasty type, bools are stored as `i32`.
+    pub fn from_type(ty: Type) -> Option<Self> {
+        match ty {
+            Type::Int => Some(ValType::I64),
+            Type::Float => Some(ValType::F64),
+            Type::Bool => Some(ValType::I32),
+            Type::String | Type::Nil => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F64 => "f64",
+        }
+    }
+}
+
+/// Single webassembly instruction, enough to be written both as text and binary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    I32Const(i32),
+    I64Const(i64),
+    F64Const(f64),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    LocalGet(u32),
+    LocalSet(u32),
+    Drop,
+    If(ValType),
+    Else,
+    End,
+    /// Instruction without immediates, given by its text name and opcode.
+    Plain(&'static str, u8),
+}
+
+/// Global holding value of a single declaration.
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: String,
+    pub ty: ValType,
+}
+
+/// Exported function returning value of a global.
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub name: String,
+    pub global: u32,
+}
+
+/// Compiled module. Declarations become mutable globals initialized by the start function,
+/// public ones are exported as functions returning their value.
+#[derive(Debug, Default)]
+pub struct WasmModule {
+    pub globals: Vec<Global>,
+    /// Locals of the start function, used as scratch space by logical operators.
+    pub locals: Vec<ValType>,
+    /// Body of the start function, without the final `end`.
+    pub start: Vec<Instruction>,
+    pub exports: Vec<Export>,
+}
+
+impl WasmModule {
+    /// Module in webassembly text format.
+    pub fn to_wat(&self) -> String {
+        let mut result = String::from("(module\n");
+        for global in &self.globals {
+            let zero = Self::zero(global.ty);
+            let _ = writeln!(result, "  (global ${} (mut {}) ({}))", global.name, global.ty.name(), zero);
+        }
+        for export in &self.exports {
+            let global = &self.globals[export.global as usize];
+            let _ = writeln!(
+                result, "  (func (export \"{}\") (result {})\n    global.get ${})",
+                export.name, global.ty.name(), global.name
+            );
+        }
+
+        result.push_str("  (func $start");
+        for local in &self.locals {
+            let _ = write!(result, " (local {})", local.name());
+        }
+        result.push('\n');
+
+        let mut depth = 2;
+        for instruction in &self.start {
+            if matches!(instruction, Instruction::Else | Instruction::End) { depth -= 1; }
+            let _ = writeln!(result, "{}{}", "  ".repeat(depth), self.instruction_text(instruction));
+            if matches!(instruction, Instruction::If(_) | Instruction::Else) { depth += 1; }
+        }
+        result.push_str("  )\n  (start $start)\n)\n");
+        result
+    }
+
+    /// Module in webassembly binary format.
+    pub fn to_wasm(&self) -> Vec<u8> {
+        binary::encode(self)
+    }
+
+    fn zero(ty: ValType) -> String {
+        format!("{}.const 0", ty.name())
+    }
+
+    fn instruction_text(&self, instruction: &Instruction) -> String {
+        match instruction {
+            Instruction::I32Const(value) => format!("i32.const {}", value),
+            Instruction::I64Const(value) => format!("i64.const {}", value),
+            Instruction::F64Const(value) if value.is_nan() => "f64.const nan".to_string(),
+            Instruction::F64Const(value) if value.is_infinite() => {
+                format!("f64.const {}inf", if *value < 0.0 { "-" } else { "" })
+            },
+            Instruction::F64Const(value) => format!("f64.const {:?}", value),
+            Instruction::GlobalGet(index) => format!("global.get ${}", self.globals[*index as usize].name),
+            Instruction::GlobalSet(index) => format!("global.set ${}", self.globals[*index as usize].name),
+            Instruction::LocalGet(index) => format!("local.get {}", index),
+            Instruction::LocalSet(index) => format!("local.set {}", index),
+            Instruction::Drop => "drop".to_string(),
+            Instruction::If(ty) => format!("if (result {})", ty.name()),
+            Instruction::Else => "else".to_string(),
+            Instruction::End => "end".to_string(),
+            Instruction::Plain(name, _) => name.to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WasmErrorTy {
+    /// Node has no type or binding, because type checker or resolver did not run.
+    MissingMetadata,
+    /// Values of this type can not be represented in webassembly yet.
+    UnsupportedType(Type),
+    UnsupportedOperator,
+    /// Literal does not fit in its webassembly type.
+    UnsupportedLiteral,
+}
+
+#[derive(Debug)]
+pub struct WasmError {
+    pub ty: WasmErrorTy,
+    pub token: Token,
+}
+
+impl WasmError {
+    pub fn new(ty: WasmErrorTy, token: Token) -> Self {
+        Self { ty, token }
+    }
+}
+
+impl HastyError for WasmError {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("WASM", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match self.ty {
+            WasmErrorTy::MissingMetadata => format!("'{}' has no type, was the program type checked?", self.token.lexeme),
+            WasmErrorTy::UnsupportedType(ty) => format!("Type {} is not supported in webassembly.", ty),
+            WasmErrorTy::UnsupportedOperator => format!("Operator '{}' is not supported.", self.token.lexeme),
+            WasmErrorTy::UnsupportedLiteral => format!("Literal '{}' is not supported.", self.token.lexeme),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        let code = match self.ty {
+            WasmErrorTy::MissingMetadata => "E0503",
+            WasmErrorTy::UnsupportedType(_) => "E0504",
+            WasmErrorTy::UnsupportedOperator => "E0501",
+            WasmErrorTy::UnsupportedLiteral => "E0502",
+        };
+        Diagnostic::error(self.get_error_description()).with_code(code).with_primary(&self.token, "")
+    }
+}
+
+/// Compiles typed AST to a webassembly module.
+/// Requires `BindingId` and `Type` metadata, so resolver and type checker have to run first.
+pub struct WasmCompiler {
+    module: WasmModule,
+    globals: HashMap<BindingId, u32>,
+    name_counts: HashMap<String, usize>,
+}
+
+impl Default for WasmCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WasmCompiler {
+    pub fn new() -> Self {
+        Self {
+            module: WasmModule::default(),
+            globals: HashMap::new(),
+            name_counts: HashMap::new(),
+        }
+    }
+
+    pub fn compile(mut self, ast: &[BoxedASTNode]) -> Result<WasmModule, WasmError> {
+        for node in ast.iter().filter(|node| !node.borrow().is_test()) {
+            let declaration = matches!(**node.borrow(), ASTNode::VarDecl { .. });
+            self.compile_node(node)?;
+            if !declaration {
+                self.emit(Instruction::Drop);
+            }
+        }
+        Ok(self.module)
+    }
+
+    fn compile_node(&mut self, node: &BoxedASTNode) -> Result<(), WasmError> {
+        let n = node.borrow();
+        let ty = Self::val_type(node)?;
+        match **n {
+            ASTNode::Binary { ref left, ref operator, ref right } => {
+                let (l, r) = (Self::val_type(left)?, Self::val_type(right)?);
+                // Only `==` and `!=` accept a bool and a number, which are never strictly equal.
+                if (l == ValType::I32) != (r == ValType::I32) {
+                    let equal = match operator.token_type {
+                        TokenType::EQUAL_EQUAL => 0,
+                        TokenType::BANG_EQUAL => 1,
+                        _ => return Err(WasmError::new(WasmErrorTy::UnsupportedOperator, operator.clone())),
+                    };
+                    self.compile_node(left)?;
+                    self.emit(Instruction::Drop);
+                    self.compile_node(right)?;
+                    self.emit(Instruction::Drop);
+                    self.emit(Instruction::I32Const(equal));
+                    return Ok(());
+                }
+
+                // Operands are converted to float if result, or the other operand, is a float.
+                let operands = if ty == ValType::F64 || l == ValType::F64 || r == ValType::F64 { ValType::F64 } else { l };
+                self.compile_operand(left, operands)?;
+                self.compile_operand(right, operands)?;
+                let instruction = Self::binary_instruction(operator, operands)
+                    .ok_or_else(|| WasmError::new(WasmErrorTy::UnsupportedOperator, operator.clone()))?;
+                self.emit(instruction);
+            },
+            ASTNode::Logical { ref left, ref operator, ref right } => {
+                // Result is one of the operands, so left one is kept in a local.
+                let local = self.local(ty);
+                self.compile_operand(left, ty)?;
+                self.emit(Instruction::LocalSet(local));
+                self.emit(Instruction::LocalGet(local));
+                self.truthy(ty);
+
+                let (then, otherwise) = match operator.token_type {
+                    TokenType::AND => (Some(right), None),
+                    TokenType::OR => (None, Some(right)),
+                    _ => return Err(WasmError::new(WasmErrorTy::UnsupportedOperator, operator.clone())),
+                };
+                self.emit(Instruction::If(ty));
+                match then {
+                    Some(right) => self.compile_operand(right, ty)?,
+                    None => self.emit(Instruction::LocalGet(local)),
+                }
+                self.emit(Instruction::Else);
+                match otherwise {
+                    Some(right) => self.compile_operand(right, ty)?,
+                    None => self.emit(Instruction::LocalGet(local)),
+                }
+                self.emit(Instruction::End);
+            },
+            ASTNode::Unary { ref operator, ref right } => {
+                let operand = Self::val_type(right)?;
+                match (&operator.token_type, operand) {
+                    (TokenType::MINUS, ValType::I64) => {
+                        self.emit(Instruction::I64Const(0));
+                        self.compile_node(right)?;
+                        self.emit(Instruction::Plain("i64.sub", 0x7d));
+                    },
+                    (TokenType::MINUS, ValType::F64) => {
+                        self.compile_node(right)?;
+                        self.emit(Instruction::Plain("f64.neg", 0x9a));
+                    },
+                    (TokenType::BANG, _) => {
+                        self.compile_node(right)?;
+                        self.truthy(operand);
+                        self.emit(Instruction::Plain("i32.eqz", 0x45));
+                    },
+                    _ => return Err(WasmError::new(WasmErrorTy::UnsupportedOperator, operator.clone())),
+                }
+            },
+            ASTNode::Literal { ref value } => self.emit(match value.token_type {
+                TokenType::INTEGER => Instruction::I64Const(value.lexeme.parse()
+                    .map_err(|_| WasmError::new(WasmErrorTy::UnsupportedLiteral, value.clone()))?),
+                TokenType::FLOATING => Instruction::F64Const(value.lexeme.parse()
+                    .map_err(|_| WasmError::new(WasmErrorTy::UnsupportedLiteral, value.clone()))?),
+                TokenType::TRUE => Instruction::I32Const(1),
+                _ => Instruction::I32Const(0),
+            }),
+            ASTNode::Grouping { ref expr } => self.compile_node(expr)?,
+            ASTNode::VarDecl { ref name, ref initializer, public, .. } => {
+                match initializer {
+                    Some(initializer) => self.compile_operand(initializer, ty)?,
+                    None => self.emit(match ty {
+                        ValType::I32 => Instruction::I32Const(0),
+                        ValType::I64 => Instruction::I64Const(0),
+                        ValType::F64 => Instruction::F64Const(0.0),
+                    }),
+                }
+
+                let id = node.meta.get::<BindingId>()
+                    .ok_or_else(|| WasmError::new(WasmErrorTy::MissingMetadata, name.clone()))?;
+                let global = self.module.globals.len() as u32;
+                let global_name = self.global_name(&name.lexeme);
+                self.module.globals.push(Global { name: global_name, ty });
+                self.globals.insert(*id, global);
+                self.emit(Instruction::GlobalSet(global));
+
+                if public {
+                    // Shadowed declarations replace the previous export.
+                    self.module.exports.retain(|export| export.name != name.lexeme);
+                    self.module.exports.push(Export { name: name.lexeme.clone(), global });
+                }
+            },
+            ASTNode::Variable { ref name } => {
+                let global = node.meta.get::<BindingId>()
+                    .and_then(|id| self.globals.get(&id).copied())
+                    .ok_or_else(|| WasmError::new(WasmErrorTy::MissingMetadata, name.clone()))?;
+                self.emit(Instruction::GlobalGet(global));
+            },
+            // Tests are only run by the test runner.
+            ASTNode::Test { .. } | ASTNode::Assert { .. } => {},
+        }
+        Ok(())
+    }
+
+    /// Compiles node, converting integer result to float if `ty` is a float.
+    fn compile_operand(&mut self, node: &BoxedASTNode, ty: ValType) -> Result<(), WasmError> {
+        self.compile_node(node)?;
+        if ty == ValType::F64 && Self::val_type(node)? == ValType::I64 {
+            self.emit(Instruction::Plain("f64.convert_i64_s", 0xb9));
+        }
+        Ok(())
+    }
+
+    /// Replaces value on top of the stack with its truthiness, same as in javascript.
+    fn truthy(&mut self, ty: ValType) {
+        match ty {
+            ValType::I32 => {},
+            ValType::I64 => {
+                self.emit(Instruction::Plain("i64.eqz", 0x50));
+                self.emit(Instruction::Plain("i32.eqz", 0x45));
+            },
+            // NaN and both zeros are falsy.
+            ValType::F64 => {
+                self.emit(Instruction::Plain("f64.abs", 0x99));
+                self.emit(Instruction::F64Const(0.0));
+                self.emit(Instruction::Plain("f64.gt", 0x64));
+            },
+        }
+    }
+
+    fn binary_instruction(operator: &Token, operands: ValType) -> Option<Instruction> {
+        use TokenType::*;
+        let (name, opcode) = match (&operator.token_type, operands) {
+            (PLUS, ValType::I64) => ("i64.add", 0x7c),
+            (MINUS, ValType::I64) => ("i64.sub", 0x7d),
+            (STAR, ValType::I64) => ("i64.mul", 0x7e),
+            (EQUAL_EQUAL, ValType::I64) => ("i64.eq", 0x51),
+            (BANG_EQUAL, ValType::I64) => ("i64.ne", 0x52),
+            (LESS, ValType::I64) => ("i64.lt_s", 0x53),
+            (GREATER, ValType::I64) => ("i64.gt_s", 0x55),
+            (LESS_EQUAL, ValType::I64) => ("i64.le_s", 0x57),
+            (GREATER_EQUAL, ValType::I64) => ("i64.ge_s", 0x59),
+            (PLUS, ValType::F64) => ("f64.add", 0xa0),
+            (MINUS, ValType::F64) => ("f64.sub", 0xa1),
+            (STAR, ValType::F64) => ("f64.mul", 0xa2),
+            (SLASH, ValType::F64) => ("f64.div", 0xa3),
+            (EQUAL_EQUAL, ValType::F64) => ("f64.eq", 0x61),
+            (BANG_EQUAL, ValType::F64) => ("f64.ne", 0x62),
+            (LESS, ValType::F64) => ("f64.lt", 0x63),
+            (GREATER, ValType::F64) => ("f64.gt", 0x64),
+            (LESS_EQUAL, ValType::F64) => ("f64.le", 0x65),
+            (GREATER_EQUAL, ValType::F64) => ("f64.ge", 0x66),
+            (EQUAL_EQUAL, ValType::I32) => ("i32.eq", 0x46),
+            (BANG_EQUAL, ValType::I32) => ("i32.ne", 0x47),
+            _ => return None,
+        };
+        Some(Instruction::Plain(name, opcode))
+    }
+
+    /// Webassembly type of an already type checked node.
+    fn val_type(node: &BoxedASTNode) -> Result<ValType, WasmError> {
+        let ty = node.meta.get::<Type>()
+            .ok_or_else(|| WasmError::new(WasmErrorTy::MissingMetadata, node.borrow().position()))?;
+        ValType::from_type(*ty)
+            .ok_or_else(|| WasmError::new(WasmErrorTy::UnsupportedType(*ty), node.borrow().position()))
+    }
+
+    /// Scratch local of the given type, shared by all logical operators.
+    /// Sharing is fine, as left operand is never read after right one is evaluated.
+    fn local(&mut self, ty: ValType) -> u32 {
+        match self.module.locals.iter().position(|local| *local == ty) {
+            Some(index) => index as u32,
+            None => {
+                self.module.locals.push(ty);
+                self.module.locals.len() as u32 - 1
+            },
+        }
+    }
+
+    /// Unique global name, `x`, `x.1`, ... for shadowed declarations.
+    fn global_name(&mut self, name: &str) -> String {
+        let count = self.name_counts.entry(name.to_string()).or_default();
+        *count += 1;
+        match *count {
+            1 => name.to_string(),
+            n => format!("{}.{}", name, n - 1),
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.module.start.push(instruction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::testing::typecheck;
+
+    use super::*;
+
+    #[track_caller]
+    fn compile(code: &str) -> Result<WasmModule, WasmError> {
+        WasmCompiler::new().compile(&typecheck(code))
+    }
+
+    #[test]
+    fn test_wat() {
+        assert_eq!(
+            compile("pub let x = 1; pub let y = x / 2 || 1.5;").unwrap().to_wat(),
+            concat!(
+                "(module\n",
+                "  (global $x (mut i64) (i64.const 0))\n",
+                "  (global $y (mut f64) (f64.const 0))\n",
+                "  (func (export \"x\") (result i64)\n    global.get $x)\n",
+                "  (func (export \"y\") (result f64)\n    global.get $y)\n",
+                "  (func $start (local f64)\n",
+                "    i64.const 1\n",
+                "    global.set $x\n",
+                "    global.get $x\n",
+                "    f64.convert_i64_s\n",
+                "    i64.const 2\n",
+                "    f64.convert_i64_s\n",
+                "    f64.div\n",
+                "    local.set 0\n",
+                "    local.get 0\n",
+                "    f64.abs\n",
+                "    f64.const 0.0\n",
+                "    f64.gt\n",
+                "    if (result f64)\n",
+                "      local.get 0\n",
+                "    else\n",
+                "      f64.const 1.5\n",
+                "    end\n",
+                "    global.set $y\n",
+                "  )\n",
+                "  (start $start)\n",
+                ")\n",
+            )
+        );
+
+        let error = compile("\"a\" + \"b\"").unwrap_err();
+        assert!(matches!(error.ty, WasmErrorTy::UnsupportedType(Type::String)));
+        let error = compile("9223372036854775808").unwrap_err();
+        assert!(matches!(error.ty, WasmErrorTy::UnsupportedLiteral));
+    }
+
+    #[test]
+    fn test_mixed_equality() {
+        let module = compile("pub let x = 1 == true; pub let y = 1.5 != false;").unwrap();
+        assert_eq!(module.start, [
+            Instruction::I64Const(1), Instruction::Drop, Instruction::I32Const(1), Instruction::Drop,
+            Instruction::I32Const(0), Instruction::GlobalSet(0),
+            Instruction::F64Const(1.5), Instruction::Drop, Instruction::I32Const(0), Instruction::Drop,
+            Instruction::I32Const(1), Instruction::GlobalSet(1),
+        ]);
+    }
+}
@@ -0,0 +1,234 @@
+//! Encoding of `WasmModule` in the webassembly binary format.
+
+use super::{Instruction, ValType, WasmModule};
+
+const MAGIC: &[u8] = b"\0asm";
+const VERSION: &[u8] = &[1, 0, 0, 0];
+
+const SECTION_TYPE: u8 = 1;
+const SECTION_FUNCTION: u8 = 3;
+const SECTION_GLOBAL: u8 = 6;
+const SECTION_EXPORT: u8 = 7;
+const SECTION_START: u8 = 8;
+const SECTION_CODE: u8 = 10;
+
+pub(super) fn encode(module: &WasmModule) -> Vec<u8> {
+    let mut result = Vec::new();
+    result.extend_from_slice(MAGIC);
+    result.extend_from_slice(VERSION);
+
+    // Start function `() -> ()` comes first, followed by one getter for every export.
+    // Getter types are `() -> T`, deduplicated by the result type.
+    let mut types: Vec<Option<ValType>> = vec![None];
+    let mut function_types = vec![0];
+    for export in &module.exports {
+        let ty = Some(module.globals[export.global as usize].ty);
+        let index = types.iter().position(|t| *t == ty).unwrap_or_else(|| {
+            types.push(ty);
+            types.len() - 1
+        });
+        function_types.push(index as u32);
+    }
+
+    section(&mut result, SECTION_TYPE, types.len(), |bytes| {
+        for ty in &types {
+            bytes.push(0x60);
+            unsigned(bytes, 0);
+            match ty {
+                Some(ty) => { unsigned(bytes, 1); bytes.push(val_type(*ty)); },
+                None => unsigned(bytes, 0),
+            }
+        }
+    });
+
+    section(&mut result, SECTION_FUNCTION, function_types.len(), |bytes| {
+        for ty in &function_types {
+            unsigned(bytes, *ty);
+        }
+    });
+
+    if !module.globals.is_empty() {
+        section(&mut result, SECTION_GLOBAL, module.globals.len(), |bytes| {
+            for global in &module.globals {
+                bytes.push(val_type(global.ty));
+                bytes.push(1);
+                instruction(bytes, &match global.ty {
+                    ValType::I32 => Instruction::I32Const(0),
+                    ValType::I64 => Instruction::I64Const(0),
+                    ValType::F64 => Instruction::F64Const(0.0),
+                });
+                instruction(bytes, &Instruction::End);
+            }
+        });
+    }
+
+    if !module.exports.is_empty() {
+        section(&mut result, SECTION_EXPORT, module.exports.len(), |bytes| {
+            for (i, export) in module.exports.iter().enumerate() {
+                unsigned(bytes, export.name.len() as u32);
+                bytes.extend_from_slice(export.name.as_bytes());
+                bytes.push(0x00);
+                unsigned(bytes, i as u32 + 1);
+            }
+        });
+    }
+
+    let mut start = Vec::new();
+    unsigned(&mut start, 0);
+    raw_section(&mut result, SECTION_START, &start);
+
+    section(&mut result, SECTION_CODE, function_types.len(), |bytes| {
+        let mut body = Vec::new();
+        unsigned(&mut body, module.locals.len() as u32);
+        for local in &module.locals {
+            unsigned(&mut body, 1);
+            body.push(val_type(*local));
+        }
+        for i in &module.start {
+            instruction(&mut body, i);
+        }
+        instruction(&mut body, &Instruction::End);
+        sized(bytes, &body);
+
+        for export in &module.exports {
+            let mut body = Vec::new();
+            unsigned(&mut body, 0);
+            instruction(&mut body, &Instruction::GlobalGet(export.global));
+            instruction(&mut body, &Instruction::End);
+            sized(bytes, &body);
+        }
+    });
+
+    result
+}
+
+fn instruction(bytes: &mut Vec<u8>, instruction: &Instruction) {
+    match instruction {
+        Instruction::I32Const(value) => { bytes.push(0x41); signed(bytes, *value as i64); },
+        Instruction::I64Const(value) => { bytes.push(0x42); signed(bytes, *value); },
+        Instruction::F64Const(value) => { bytes.push(0x44); bytes.extend_from_slice(&value.to_le_bytes()); },
+        Instruction::GlobalGet(index) => { bytes.push(0x23); unsigned(bytes, *index); },
+        Instruction::GlobalSet(index) => { bytes.push(0x24); unsigned(bytes, *index); },
+        Instruction::LocalGet(index) => { bytes.push(0x20); unsigned(bytes, *index); },
+        Instruction::LocalSet(index) => { bytes.push(0x21); unsigned(bytes, *index); },
+        Instruction::Drop => bytes.push(0x1a),
+        Instruction::If(ty) => { bytes.push(0x04); bytes.push(val_type(*ty)); },
+        Instruction::Else => bytes.push(0x05),
+        Instruction::End => bytes.push(0x0b),
+        Instruction::Plain(_, opcode) => bytes.push(*opcode),
+    }
+}
+
+fn val_type(ty: ValType) -> u8 {
+    match ty {
+        ValType::I32 => 0x7f,
+        ValType::I64 => 0x7e,
+        ValType::F64 => 0x7c,
+    }
+}
+
+/// Writes section consisting of a vector with `count` items.
+fn section(bytes: &mut Vec<u8>, id: u8, count: usize, items: impl FnOnce(&mut Vec<u8>)) {
+    let mut content = Vec::new();
+    unsigned(&mut content, count as u32);
+    items(&mut content);
+    raw_section(bytes, id, &content);
+}
+
+fn raw_section(bytes: &mut Vec<u8>, id: u8, content: &[u8]) {
+    bytes.push(id);
+    sized(bytes, content);
+}
+
+/// Writes content prefixed by its size.
+fn sized(bytes: &mut Vec<u8>, content: &[u8]) {
+    unsigned(bytes, content.len() as u32);
+    bytes.extend_from_slice(content);
+}
+
+/// Unsigned LEB128.
+fn unsigned(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Signed LEB128.
+fn signed(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::wasm::{Export, Global};
+
+    /// Reads unsigned LEB128, returning value and number of bytes read.
+    fn read_unsigned(bytes: &[u8]) -> (u32, usize) {
+        let (mut value, mut shift, mut read) = (0, 0, 0);
+        loop {
+            let byte = bytes[read];
+            read += 1;
+            value |= ((byte & 0x7f) as u32) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 { return (value, read); }
+        }
+    }
+
+    #[test]
+    fn test_leb128() {
+        let mut bytes = Vec::new();
+        unsigned(&mut bytes, 624485);
+        assert_eq!(bytes, [0xe5, 0x8e, 0x26]);
+
+        bytes.clear();
+        signed(&mut bytes, -123456);
+        assert_eq!(bytes, [0xc0, 0xbb, 0x78]);
+
+        bytes.clear();
+        signed(&mut bytes, 64);
+        assert_eq!(bytes, [0xc0, 0x00]);
+    }
+
+    #[test]
+    fn test_module_structure() {
+        let module = WasmModule {
+            globals: vec![Global { name: "x".to_string(), ty: ValType::I64 }],
+            locals: vec![],
+            start: vec![Instruction::I64Const(42), Instruction::GlobalSet(0)],
+            exports: vec![Export { name: "x".to_string(), global: 0 }],
+        };
+        let bytes = module.to_wasm();
+        assert_eq!(&bytes[..8], b"\0asm\x01\0\0\0");
+
+        // Sections have to appear in increasing order and cover the whole module.
+        let mut sections = Vec::new();
+        let mut offset = 8;
+        while offset < bytes.len() {
+            let id = bytes[offset];
+            let (size, read) = read_unsigned(&bytes[offset + 1..]);
+            sections.push(id);
+            offset += 1 + read + size as usize;
+        }
+        assert_eq!(offset, bytes.len());
+        assert_eq!(sections, [SECTION_TYPE, SECTION_FUNCTION, SECTION_GLOBAL, SECTION_EXPORT, SECTION_START, SECTION_CODE]);
+
+        // Start function body: no locals, `i64.const 42`, `global.set 0`, `end`.
+        assert!(bytes.windows(7).any(|w| w == [0x00, 0x42, 0x2a, 0x24, 0x00, 0x0b, 0x04]));
+    }
+}
@@ -1,77 +1,411 @@
-use nauvi::module::{Module, block::Statement, block::Block};
+use std::{collections::HashMap, time::Instant};
+
+use nauvi::module::{Module, block::Statement};
 
 use crate::parser::ast::{BoxedASTNode, ASTNode};
+use crate::passes::resolver::BindingId;
+use crate::scanner::{Token, TokenType};
+use crate::util::{diagnostic::Diagnostic, HastyError, json, trace::Tracer, unified_error};
+
+use self::output::Output;
+use self::sourcemap::{SourceMap, SourceMapOptions};
+
+mod minify;
+mod output;
+pub mod sourcemap;
+
+/// Words that can not be used as identifiers in javascript.
+const JS_RESERVED: &[&str] = &[
+    "await", "break", "case", "catch", "class", "const", "continue", "debugger",
+    "default", "delete", "do", "else", "enum", "export", "extends", "false",
+    "finally", "for", "function", "if", "implements", "import", "in", "instanceof",
+    "interface", "let", "new", "null", "package", "private", "protected", "public",
+    "return", "static", "super", "switch", "this", "throw", "true", "try",
+    "typeof", "var", "void", "while", "with", "yield", "arguments", "eval", "undefined",
+];
+
+#[derive(Debug)]
+pub enum CompileErrorTy {
+    /// Operator has no javascript equivalent.
+    UnsupportedOperator,
+    /// Literal of this type can not be compiled yet.
+    UnsupportedLiteral,
+}
+
+#[derive(Debug)]
+pub struct CompileError {
+    pub ty: CompileErrorTy,
+    pub token: Token,
+}
+
+impl CompileError {
+    pub fn new(ty: CompileErrorTy, token: Token) -> Self {
+        Self {
+            ty,
+            token,
+        }
+    }
+}
+
+impl HastyError for CompileError {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("COMPILER", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match self.ty {
+            CompileErrorTy::UnsupportedOperator => format!("Operator '{}' is not supported.", self.token.lexeme),
+            CompileErrorTy::UnsupportedLiteral => format!("Literal '{}' is not supported.", self.token.lexeme),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        let code = match self.ty {
+            CompileErrorTy::UnsupportedOperator => "E0501",
+            CompileErrorTy::UnsupportedLiteral => "E0502",
+        };
+        Diagnostic::error(self.get_error_description()).with_code(code).with_primary(&self.token, "")
+    }
+}
+
+/// Version of ECMAScript that compiled code has to run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EcmaVersion {
+    /// No block scoped declarations, `var` is used instead.
+    Es5,
+    Es2015,
+    Es2020,
+}
+
+/// How compiled module is wrapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleFormat {
+    /// ECMAScript module (always strict).
+    Esm,
+    /// CommonJS module, as used by node.
+    CommonJs,
+    /// Immediately invoked function expression, for plain `<script>` tags.
+    Iife,
+}
+
+/// Options that change how the program is compiled.
+#[derive(Debug, Clone)]
+pub struct CompilerOptions {
+    /// Name of the generated module.
+    pub module_name: String,
+    pub target: EcmaVersion,
+    pub format: ModuleFormat,
+    /// Add `"use strict"` directive (ECMAScript modules are always strict).
+    pub strict: bool,
+    /// Emit code without unnecessary whitespace.
+    pub minify: bool,
+    /// String used for a single level of indentation.
+    pub indent: String,
+    /// Write trace of every compiled node (stderr by default, see `Compiler::trace_to`).
+    pub trace: bool,
+    /// Generate source map and append `//# sourceMappingURL` comment to the module.
+    pub source_map: Option<SourceMapOptions>,
+}
+
+impl Default for CompilerOptions {
+    fn default() -> Self {
+        Self {
+            module_name: "comp_result".to_string(),
+            target: EcmaVersion::Es2015,
+            format: ModuleFormat::Esm,
+            strict: false,
+            minify: false,
+            indent: "    ".to_string(),
+            trace: false,
+            source_map: None,
+        }
+    }
+}
 
 /// Compiler that compiles a program to a javascript.
 pub struct Compiler {
+    options: CompilerOptions,
+    tracer: Option<Tracer>,
+    /// Javascript names of resolved bindings.
+    names: HashMap<BindingId, String>,
+    /// How many bindings already use given name.
+    name_counts: HashMap<String, usize>,
+    /// How many short names were generated for minified output.
+    short_names: usize,
+    /// Public declarations of each name that were not compiled yet, only the last one is exported.
+    pending_exports: HashMap<String, usize>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Compiler {
     pub fn new() -> Self {
-        Self { }
+        Self::with_options(CompilerOptions::default())
+    }
+
+    pub fn with_options(options: CompilerOptions) -> Self {
+        Self {
+            tracer: if options.trace { Some(Tracer::stderr()) } else { None },
+            options,
+            names: HashMap::new(),
+            name_counts: HashMap::new(),
+            short_names: 0,
+            pending_exports: HashMap::new(),
+        }
+    }
+
+    /// Sets where trace is written, if it is enabled in options.
+    pub fn trace_to(&mut self, tracer: Tracer) -> &mut Self {
+        if self.options.trace {
+            self.tracer = Some(tracer);
+        }
+        self
     }
 
-    pub fn compile_ast(&mut self, ast: &Vec<BoxedASTNode>, target: &mut impl std::io::Write) {
-        let mut module = Module::create("comp_result");
+    /// Compiles program into a module, returning source map if it was enabled in options.
+    pub fn compile_ast(&mut self, ast: &[BoxedASTNode], target: &mut impl std::io::Write) -> Result<Option<SourceMap>, CompileError> {
+        let started = Instant::now();
+        let mut module = Module::create(&self.options.module_name);
+        let mut source_map = self.options.source_map.as_ref().map(SourceMap::new);
+        let mut output = Output::new(self.options.minify, &self.options.indent);
 
+        let iife = self.options.format == ModuleFormat::Iife;
+        if iife {
+            output.open(if self.options.minify { "(function(){" } else { "(function () {" });
+        }
+        if self.options.strict && self.options.format != ModuleFormat::Esm {
+            output.statement("\"use strict\";");
+        }
+
+        self.pending_exports.clear();
         for node in ast {
-            if let Some(statement) = Self::compile_node(node) {
-                module.stmt(statement);
+            if let ASTNode::VarDecl { ref name, public: true, .. } = **node.borrow() {
+                *self.pending_exports.entry(name.lexeme.clone()).or_default() += 1;
+            }
+        }
+
+        // Statements are rendered one by one to know where each of them starts.
+        for node in ast.iter().filter(|node| !node.borrow().is_test()) {
+            let (statement, names) = if self.options.minify {
+                self.minify_statement(node)?
+            } else {
+                (Self::render(self.compile_node(node)?), Vec::new())
+            };
+
+            let position = output.statement(&statement);
+            if let Some(source_map) = &mut source_map {
+                source_map.map_statement(position, &statement, node);
+                for name in names {
+                    source_map.map_name((position.0, position.1 + name.column), name.offset, &name.name);
+                }
             }
         }
 
+        if iife {
+            output.close("})();");
+        }
+
+        let code = output.finish();
+        if !code.is_empty() {
+            module.stmt(Statement::Literal { value: code });
+        }
         module.generate_to(target);
+
+        if let Some(options) = &self.options.source_map {
+            let _ = writeln!(target, "//# sourceMappingURL={}", options.url);
+        }
+
+        if let Some(tracer) = &self.tracer {
+            tracer.pass("codegen", started.elapsed());
+        }
+        Ok(source_map)
     }
 
-    pub fn compile_node(node: &BoxedASTNode) -> Option<Statement> {
-        let n = node.node.borrow();
-        match **n {
+    pub fn compile_node(&mut self, node: &BoxedASTNode) -> Result<Statement, CompileError> {
+        let started = Instant::now();
+        let result = self.lower_node(node);
+
+        if let Some(tracer) = &self.tracer {
+            tracer.node("codegen", &node.borrow(), started.elapsed());
+        }
+        result
+    }
+
+    /// Lowers single node into nauvi statement.
+    fn lower_node(&mut self, node: &BoxedASTNode) -> Result<Statement, CompileError> {
+        let n = node.borrow();
+        Ok(match **n {
             ASTNode::Binary { ref left, ref operator, ref right } => {
-                println!("binary");
-                let left = Self::compile_node(&left)?;
-                let right = Self::compile_node(&right)?;
-                Some(Statement::Binary { 
-                    left: left.boxed(),
-                    operator: operator.lexeme.clone(),
-                    right: right.boxed()
-                })
+                Statement::Binary {
+                    left: self.compile_node(left)?.boxed(),
+                    operator: Self::compile_operator(operator)?.to_string(),
+                    right: self.compile_node(right)?.boxed()
+                }
             },
             ASTNode::Logical { ref left, ref operator, ref right } => {
-                let left = Self::compile_node(&left)?;
-                let right = Self::compile_node(&right)?;
-                let mut block = Block::new(0);
-                block.binary(left, &operator.lexeme, right);   
-                Some(Statement::Block(Box::new(block)))
+                Statement::Binary {
+                    left: self.compile_node(left)?.boxed(),
+                    operator: Self::compile_operator(operator)?.to_string(),
+                    right: self.compile_node(right)?.boxed()
+                }
             },
-            ASTNode::Unary { operator: _, right: _ } => {
-                todo!("unary")
+            ASTNode::Unary { ref operator, ref right } => {
+                let operator = Self::compile_operator(operator)?;
+                let right = Self::render(self.compile_node(right)?);
+                // Avoid producing `--x`, which would be a decrement.
+                let value = if right.starts_with(operator) {
+                    format!("{}({})", operator, right)
+                } else {
+                    format!("{}{}", operator, right)
+                };
+                Statement::Literal { value }
             },
             ASTNode::Literal { ref value } => {
-                println!("literal");
-                Some(Statement::Literal { value: value.lexeme.clone() })
+                Statement::Literal { value: Self::compile_literal(value)? }
             },
-            ASTNode::Grouping { expr: _ } => {
-                todo!("grouping")
+            // Binary expressions are always wrapped in parentheses, so grouping
+            // does not need any additional output.
+            ASTNode::Grouping { ref expr } => self.compile_node(expr)?,
+            ASTNode::VarDecl { name: ref token, ref initializer, constant, public, .. } => {
+                let name = self.declare(node, token);
+                let keyword = match (self.options.target, constant) {
+                    (EcmaVersion::Es5, _) => "var",
+                    (_, true) => "const",
+                    (_, false) => "let",
+                };
+                let mut value = match initializer {
+                    Some(initializer) => {
+                        let initializer = Self::render(self.compile_node(initializer)?);
+                        format!("{} {} = {};", keyword, name, initializer)
+                    },
+                    None => format!("{} {};", keyword, name),
+                };
+                if let Some(export) = public.then(|| self.export(&name, &token.lexeme)).flatten() {
+                    value = format!("{}\n{}", value, export);
+                }
+                Statement::Literal { value }
             },
-            // TODO: Add support for variable declarations and references (requires additional passes before compilation to resolve shadowing and such)
-            _ => None
+            ASTNode::Variable { ref name } => {
+                let value = node.meta.get::<BindingId>()
+                    .and_then(|id| self.names.get(&id).cloned())
+                    .unwrap_or_else(|| Self::escape_name(&name.lexeme));
+                Statement::Literal { value }
+            },
+            // Tests are only run by the test runner.
+            ASTNode::Test { .. } | ASTNode::Assert { .. } => Statement::Literal { value: String::new() },
+        })
+    }
+
+    /// Statement exporting binding under its original name, if module format supports exports.
+    /// Names declared more than once are only exported by their last declaration.
+    fn export(&mut self, name: &str, exported: &str) -> Option<String> {
+        if let Some(pending) = self.pending_exports.get_mut(exported) {
+            *pending = pending.saturating_sub(1);
+            if *pending > 0 { return None; }
+        }
+
+        let space = if self.options.minify { "" } else { " " };
+        match self.options.format {
+            ModuleFormat::Esm if name == exported => Some(format!("export{}{{{}{}{}}};", space, space, name, space)),
+            ModuleFormat::Esm => Some(format!("export{}{{{}{} as {}{}}};", space, space, name, exported, space)),
+            ModuleFormat::CommonJs => Some(format!("exports.{}{}={}{};", exported, space, space, name)),
+            ModuleFormat::Iife => None,
         }
     }
+
+    /// Picks javascript name for a declaration.
+    /// Shadowed bindings get a numeric suffix, as javascript does not allow redeclaring `let`.
+    /// In minified output every binding gets its own short name instead.
+    fn declare(&mut self, node: &BoxedASTNode, name: &Token) -> String {
+        let base = Self::escape_name(&name.lexeme);
+        let Some(id) = node.meta.get::<BindingId>() else { return base };
+
+        let public = matches!(**node.borrow(), ASTNode::VarDecl { public: true, .. });
+        if self.options.minify && !public {
+            let name = self.next_short_name();
+            self.names.insert(*id, name.clone());
+            return name;
+        }
+
+        let count = self.name_counts.entry(base.clone()).or_insert(0);
+        let name = if *count == 0 { base } else { format!("{}${}", base, count) };
+        *count += 1;
+
+        self.names.insert(*id, name.clone());
+        name
+    }
+
+    /// Makes sure identifier is not a javascript keyword.
+    pub(crate) fn escape_name(name: &str) -> String {
+        if JS_RESERVED.contains(&name) { format!("{}$", name) } else { name.to_string() }
+    }
+
+    /// Quotes string value, escaping characters that are not allowed in javascript string literals.
+    fn string_literal(value: &str) -> String {
+        // JSON strings are valid in javascript, except for these line terminators before ES2019.
+        json::quote(value).replace('\u{2028}', "\\u2028").replace('\u{2029}', "\\u2029")
+    }
+
+    fn compile_operator(operator: &Token) -> Result<&'static str, CompileError> {
+        Ok(match operator.token_type {
+            TokenType::PLUS => "+",
+            TokenType::MINUS => "-",
+            TokenType::STAR => "*",
+            TokenType::SLASH => "/",
+            TokenType::BANG => "!",
+            TokenType::EQUAL_EQUAL => "===",
+            TokenType::BANG_EQUAL => "!==",
+            TokenType::LESS => "<",
+            TokenType::LESS_EQUAL => "<=",
+            TokenType::GREATER => ">",
+            TokenType::GREATER_EQUAL => ">=",
+            TokenType::AND => "&&",
+            TokenType::OR => "||",
+            _ => return Err(CompileError::new(CompileErrorTy::UnsupportedOperator, operator.clone())),
+        })
+    }
+
+    fn compile_literal(value: &Token) -> Result<String, CompileError> {
+        Ok(match value.token_type {
+            TokenType::STRING => match value.data.as_ref().and_then(|data| data.downcast_ref::<String>()) {
+                Some(data) => Self::string_literal(data),
+                None => return Err(CompileError::new(CompileErrorTy::UnsupportedLiteral, value.clone())),
+            },
+            TokenType::INTEGER | TokenType::FLOATING | TokenType::TRUE | TokenType::FALSE => value.lexeme.clone(),
+            TokenType::NIL => "null".to_string(),
+            _ => return Err(CompileError::new(CompileErrorTy::UnsupportedLiteral, value.clone())),
+        })
+    }
+
+    /// Renders single statement, so that it can be embedded into another one.
+    fn render(statement: Statement) -> String {
+        let mut module = Module::create("");
+        module.stmt(statement);
+
+        let mut result = Vec::new();
+        module.generate_to(&mut result);
+        String::from_utf8(result).unwrap().trim_end().to_string()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::compiler::Compiler;
+    use crate::compiler::{Compiler, CompilerOptions, EcmaVersion, ModuleFormat, output::Output, sourcemap::SourceMapOptions};
+    use crate::util::{testing::{parse, resolve, SharedBuffer}, trace::Tracer};
 
     #[track_caller]
     fn compile(code: &str) -> String {
-        let scanner = crate::scanner::Scanner::new(code);
-        let tokens = scanner.scan().unwrap();
-        let parser = crate::parser::Parser::new(tokens);
-        let ast = parser.parse().unwrap();
+        let ast = resolve(code);
         let mut compiler = Compiler::new();
         let mut result = Vec::new();
-        compiler.compile_ast(&ast, &mut result);
+        compiler.compile_ast(&ast, &mut result).unwrap();
         String::from_utf8(result).unwrap()
     }
 
@@ -79,8 +413,146 @@ mod tests {
     fn test_compile_binary_expr() {
         let result = compile("1 + 2 * 2");
         assert_eq!(
-            "(1 + (2 * 2))\n",
+            "(1 + (2 * 2));\n",
             result
         )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_compile_operators() {
+        assert_eq!(
+            compile("-(1 - -2) == 3 && !true != nil"),
+            "((-(1 - -2) === 3) && (!true !== null));\n"
+        );
+        // Statements on separate lines must not be joined into a call.
+        assert_eq!(compile("let a = 1; a (-1)"), "let a = 1;\na;\n-1;\n");
+    }
+
+    #[test]
+    fn test_compile_strings() {
+        // Hasty strings have no escape sequences, so backslashes and newlines are kept as they are.
+        assert_eq!(compile("\"back\\slash\nnew line\u{2028}\""), "\"back\\\\slash\\nnew line\\u2028\";\n");
+    }
+
+    #[test]
+    fn test_empty_statements() {
+        for minify in [false, true] {
+            let mut output = Output::new(minify, "    ");
+            output.statement("");
+            output.statement("a");
+            output.statement("");
+            assert_eq!(output.finish(), "a;");
+        }
+    }
+
+    #[test]
+    fn test_compile_var_decl() {
+        assert_eq!(
+            compile("let x: int = 1; const new = x; let x = x + new; x"),
+            "let x = 1;\nconst new$ = x;\nlet x$1 = (x + new$);\nx$1;\n"
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        let ast = parse("1 + 2");
+        let trace = SharedBuffer::default();
+
+        let mut compiler = Compiler::with_options(CompilerOptions { trace: true, ..Default::default() });
+        compiler.trace_to(Tracer::new(trace.clone()));
+        let mut result = Vec::new();
+        compiler.compile_ast(&ast, &mut result).unwrap();
+
+        let trace = trace.contents();
+        let events: Vec<&str> = trace.lines().map(|l| &l[..l.find(" time=").unwrap()]).collect();
+        assert_eq!(events, vec![
+            "pass=codegen node=Literal line=0 start=0",
+            "pass=codegen node=Literal line=0 start=4",
+            "pass=codegen node=Binary line=0 start=2",
+            "pass=codegen",
+        ]);
+    }
+
+    #[test]
+    fn test_source_map() {
+        let source = "let x = 1;\n\n  x + 2";
+        let ast = parse(source);
+
+        let mut compiler = Compiler::with_options(CompilerOptions {
+            source_map: Some(SourceMapOptions {
+                file: "main.js".to_string(),
+                source_name: "main.hasty".to_string(),
+                source: source.to_string(),
+                url: "main.js.map".to_string(),
+            }),
+            ..Default::default()
+        });
+        let mut result = Vec::new();
+        let source_map = compiler.compile_ast(&ast, &mut result).unwrap().unwrap();
+
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "let x = 1;\n(x + 2);\n//# sourceMappingURL=main.js.map\n"
+        );
+        assert_eq!(source_map.encode_mappings(), "AAAI;AAEF");
+        assert_eq!(
+            source_map.to_json(),
+            "{\"version\":3,\"file\":\"main.js\",\"sources\":[\"main.hasty\"],\
+            \"sourcesContent\":[\"let x = 1;\\n\\n  x + 2\"],\"names\":[],\"mappings\":\"AAAI;AAEF\"}"
+        );
+    }
+
+    #[track_caller]
+    fn compile_with(code: &str, options: CompilerOptions) -> String {
+        let ast = resolve(code);
+        let mut result = Vec::new();
+        Compiler::with_options(options).compile_ast(&ast, &mut result).unwrap();
+        String::from_utf8(result).unwrap()
+    }
+
+    #[test]
+    fn test_module_formats() {
+        assert_eq!(
+            compile_with("const x = 1; x + 1", CompilerOptions {
+                format: ModuleFormat::Iife,
+                strict: true,
+                indent: "  ".to_string(),
+                ..Default::default()
+            }),
+            "(function () {\n  \"use strict\";\n  const x = 1;\n  (x + 1);\n})();\n"
+        );
+        assert_eq!(
+            compile_with("let x = 1; let x = 2;", CompilerOptions {
+                format: ModuleFormat::CommonJs,
+                target: EcmaVersion::Es5,
+                strict: true,
+                ..Default::default()
+            }),
+            "\"use strict\";\nvar x = 1;\nvar x$1 = 2;\n"
+        );
+        assert_eq!(
+            compile_with("let x = 1; pub const x = x; pub let y = 2;", CompilerOptions::default()),
+            "let x = 1;\nconst x$1 = x;\nexport { x$1 as x };\nlet y = 2;\nexport { y };\n"
+        );
+        assert_eq!(
+            compile_with("pub let x = 1; let x = 2; pub let x = x + 1;", CompilerOptions::default()),
+            "let x = 1;\nlet x$1 = 2;\nlet x$2 = (x$1 + 1);\nexport { x$2 as x };\n"
+        );
+        assert_eq!(
+            compile_with("pub let value = 1; let other = value;", CompilerOptions {
+                format: ModuleFormat::CommonJs,
+                minify: true,
+                ..Default::default()
+            }),
+            "let value=1;exports.value=value;let a=value;\n"
+        );
+        assert_eq!(
+            compile_with("let x = 1; x", CompilerOptions {
+                format: ModuleFormat::Iife,
+                minify: true,
+                ..Default::default()
+            }),
+            "(function(){let a=1;a;})();\n"
+        );
+    }
+}
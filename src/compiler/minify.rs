@@ -0,0 +1,236 @@
+use std::time::Instant;
+
+use crate::{parser::ast::{ASTNode, BoxedASTNode}, passes::resolver::BindingId, scanner::TokenType};
+
+use super::{Compiler, CompileError, EcmaVersion, JS_RESERVED};
+
+/// Precedence of expressions that never need parentheses.
+const PRIMARY: u8 = 20;
+/// Precedence of prefix operators.
+const PREFIX: u8 = 14;
+
+/// Identifier written into minified code, recorded for the source map.
+#[derive(Debug, Clone)]
+pub(crate) struct NameMapping {
+    /// Column in generated statement.
+    pub column: usize,
+    /// Offset of the identifier in source.
+    pub offset: usize,
+    /// Original name of the identifier.
+    pub name: String,
+}
+
+impl Compiler {
+    /// Compiles statement without any unnecessary whitespace and parentheses.
+    pub(super) fn minify_statement(&mut self, node: &BoxedASTNode) -> Result<(String, Vec<NameMapping>), CompileError> {
+        let mut code = String::new();
+        let mut names = Vec::new();
+        self.minify_node(node, &mut code, &mut names)?;
+        Ok((code, names))
+    }
+
+    fn minify_node(&mut self, node: &BoxedASTNode, code: &mut String, names: &mut Vec<NameMapping>) -> Result<(), CompileError> {
+        let started = Instant::now();
+        let n = node.borrow();
+        match **n {
+            ASTNode::Binary { ref left, ref operator, ref right }
+            | ASTNode::Logical { ref left, ref operator, ref right } => {
+                let precedence = Self::operator_precedence(&operator.token_type);
+                let operator = Self::compile_operator(operator)?;
+
+                // Operators are left associative, so only right operand
+                // needs parentheses when precedence is the same.
+                self.minify_operand(left, Self::precedence(left) < precedence, code, names)?;
+                code.push_str(operator);
+                let start = code.len();
+                self.minify_operand(right, Self::precedence(right) <= precedence, code, names)?;
+                Self::separate(code, names, start, operator);
+            },
+            ASTNode::Unary { ref operator, ref right } => {
+                let operator = Self::compile_operator(operator)?;
+                code.push_str(operator);
+                let start = code.len();
+                self.minify_operand(right, Self::precedence(right) < PREFIX, code, names)?;
+                Self::separate(code, names, start, operator);
+            },
+            ASTNode::Literal { ref value } => code.push_str(&Self::compile_literal(value)?),
+            ASTNode::Grouping { ref expr } => self.minify_node(expr, code, names)?,
+            ASTNode::VarDecl { ref name, ref initializer, constant, public, .. } => {
+                code.push_str(match (self.options.target, constant) {
+                    (EcmaVersion::Es5, _) => "var ",
+                    (_, true) => "const ",
+                    (_, false) => "let ",
+                });
+
+                let declared = self.declare(node, name);
+                names.push(NameMapping { column: code.len(), offset: name.start, name: name.lexeme.clone() });
+                code.push_str(&declared);
+
+                if let Some(initializer) = initializer {
+                    code.push('=');
+                    self.minify_node(initializer, code, names)?;
+                }
+                code.push(';');
+
+                if let Some(export) = public.then(|| self.export(&declared, &name.lexeme)).flatten() {
+                    code.push_str(&export);
+                }
+            },
+            ASTNode::Variable { ref name } => {
+                let value = node.meta.get::<BindingId>()
+                    .and_then(|id| self.names.get(&id).cloned())
+                    .unwrap_or_else(|| Self::escape_name(&name.lexeme));
+                names.push(NameMapping { column: code.len(), offset: name.start, name: name.lexeme.clone() });
+                code.push_str(&value);
+            },
+            // Tests are only run by the test runner.
+            ASTNode::Test { .. } | ASTNode::Assert { .. } => {},
+        }
+
+        if let Some(tracer) = &self.tracer {
+            tracer.node("codegen", &n, started.elapsed());
+        }
+        Ok(())
+    }
+
+    fn minify_operand(&mut self, node: &BoxedASTNode, parenthesize: bool, code: &mut String, names: &mut Vec<NameMapping>) -> Result<(), CompileError> {
+        if parenthesize { code.push('('); }
+        self.minify_node(node, code, names)?;
+        if parenthesize { code.push(')'); }
+        Ok(())
+    }
+
+    /// Inserts space between operator and operand if they would merge
+    /// into a different token, like `a- -b` or `- -a`. Names already recorded
+    /// in the operand are moved along with it.
+    fn separate(code: &mut String, names: &mut [NameMapping], operand_start: usize, operator: &str) {
+        let operand = &code[operand_start..];
+        if (operator.ends_with('-') && operand.starts_with('-'))
+            || (operator.ends_with('+') && operand.starts_with('+')) {
+            code.insert(operand_start, ' ');
+            for name in names.iter_mut().filter(|name| name.column >= operand_start) {
+                name.column += 1;
+            }
+        }
+    }
+
+    /// Precedence of an expression, as in javascript.
+    fn precedence(node: &BoxedASTNode) -> u8 {
+        match **node.borrow() {
+            ASTNode::Binary { ref operator, .. } | ASTNode::Logical { ref operator, .. } => {
+                Self::operator_precedence(&operator.token_type)
+            },
+            ASTNode::Unary { .. } => PREFIX,
+            // Folded negative numbers behave like prefix operators.
+            ASTNode::Literal { ref value } if value.lexeme.starts_with('-') => PREFIX,
+            ASTNode::Grouping { ref expr } => Self::precedence(expr),
+            _ => PRIMARY,
+        }
+    }
+
+    fn operator_precedence(operator: &TokenType) -> u8 {
+        match operator {
+            TokenType::OR => 3,
+            TokenType::AND => 4,
+            TokenType::EQUAL_EQUAL | TokenType::BANG_EQUAL => 8,
+            TokenType::LESS | TokenType::LESS_EQUAL | TokenType::GREATER | TokenType::GREATER_EQUAL => 9,
+            TokenType::PLUS | TokenType::MINUS => 11,
+            TokenType::STAR | TokenType::SLASH => 12,
+            _ => PRIMARY,
+        }
+    }
+
+    /// Shortest unused identifier: `a`, `b`, ..., `Z`, `aa`, `ab`, ...
+    pub(super) fn next_short_name(&mut self) -> String {
+        const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+        loop {
+            let mut index = self.short_names;
+            self.short_names += 1;
+
+            let mut name = Vec::new();
+            loop {
+                name.push(ALPHABET[index % ALPHABET.len()]);
+                index /= ALPHABET.len();
+                if index == 0 { break; }
+                index -= 1;
+            }
+            name.reverse();
+
+            let name = String::from_utf8(name).unwrap();
+            if !JS_RESERVED.contains(&name.as_str()) {
+                return name;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compiler::{sourcemap::SourceMapOptions, Compiler, CompilerOptions},
+        util::testing::resolve,
+    };
+
+    #[track_caller]
+    fn minify(code: &str) -> String {
+        let ast = resolve(code);
+        let mut result = Vec::new();
+        Compiler::with_options(CompilerOptions { minify: true, ..Default::default() })
+            .compile_ast(&ast, &mut result)
+            .unwrap();
+        String::from_utf8(result).unwrap()
+    }
+
+    #[test]
+    fn test_minify_parentheses() {
+        assert_eq!(minify("(1 + 2) * 3 - (4 - 5) - (6 * 7)"), "(1+2)*3-(4-5)-6*7;\n");
+        assert_eq!(minify("1 - -2 == -(-3) || (true && false)"), "1- -2===- -3||true&&false;\n");
+        assert_eq!(minify("!(1 < 2) && (nil || 1)"), "!(1<2)&&(null||1);\n");
+    }
+
+    #[test]
+    fn test_minify_names() {
+        assert_eq!(
+            minify("let first = 1; const second = first; let first = first + second;"),
+            "let a=1;const b=a;let c=a+b;\n"
+        );
+
+        let mut compiler = Compiler::new();
+        let names: Vec<String> = (0..300).map(|_| compiler.next_short_name()).collect();
+        assert_eq!(&names[..3], &["a", "b", "c"]);
+        assert_eq!(&names[51..53], &["Z", "aa"]);
+        // `do` is a keyword, so it is skipped.
+        assert_eq!(&names[221..223], &["dn", "dp"]);
+    }
+
+    #[test]
+    fn test_minify_separated_names() {
+        let ast = resolve("let x = 1; x - -x");
+
+        let mut compiler = Compiler::with_options(CompilerOptions { minify: true, ..Default::default() });
+        compiler.minify_statement(&ast[0]).unwrap();
+        let (code, names) = compiler.minify_statement(&ast[1]).unwrap();
+        assert_eq!(code, "a- -a");
+        let columns: Vec<usize> = names.iter().map(|name| name.column).collect();
+        assert_eq!(columns, [0, 4]);
+    }
+
+    #[test]
+    fn test_minify_source_map() {
+        let source = "let value = 1;\nvalue";
+        let ast = resolve(source);
+
+        let mut result = Vec::new();
+        let source_map = Compiler::with_options(CompilerOptions {
+            minify: true,
+            source_map: Some(SourceMapOptions { source: source.to_string(), ..Default::default() }),
+            ..Default::default()
+        }).compile_ast(&ast, &mut result).unwrap().unwrap();
+
+        assert_eq!(String::from_utf8(result).unwrap(), "let a=1;a;\n//# sourceMappingURL=\n");
+        assert_eq!(source_map.names, vec!["value"]);
+        // `let a=1;` maps to `value` declaration, `a` after it to its use.
+        assert_eq!(source_map.encode_mappings(), "AAAI,IAAAA,IACJ,AAAAA");
+    }
+}
@@ -0,0 +1,75 @@
+/// Collects generated code of a module, keeping track of the current position.
+pub(crate) struct Output {
+    minify: bool,
+    indent: String,
+    depth: usize,
+    lines: Vec<String>,
+}
+
+impl Output {
+    pub fn new(minify: bool, indent: &str) -> Self {
+        Self {
+            minify,
+            indent: indent.to_string(),
+            depth: 0,
+            lines: vec![String::new()],
+        }
+    }
+
+    /// Line and column at which next statement will start.
+    fn position(&self) -> (usize, usize) {
+        if self.minify {
+            (0, self.lines[0].len())
+        } else {
+            (self.lines.len() - 1, self.indent.len() * self.depth)
+        }
+    }
+
+    /// Appends statement, returning line and column at which it starts.
+    /// Statements that do not open a block are always terminated with `;`,
+    /// as javascript could otherwise join them with the next line. Empty statements are skipped.
+    pub fn statement(&mut self, code: &str) -> (usize, usize) {
+        let position = self.position();
+        if code.is_empty() {
+            return position;
+        }
+        let terminator = if code.ends_with(';') || code.ends_with('{') { "" } else { ";" };
+
+        if self.minify {
+            let line = &mut self.lines[0];
+            line.push_str(code);
+            line.push_str(terminator);
+        } else {
+            let indent = self.indent.repeat(self.depth);
+            for code_line in code.lines() {
+                let last = self.lines.len() - 1;
+                self.lines[last] = format!("{}{}", indent, code_line);
+                self.lines.push(String::new());
+            }
+            let last = self.lines.len() - 2;
+            self.lines[last].push_str(terminator);
+        }
+
+        position
+    }
+
+    /// Appends statement that opens a block, increasing indentation.
+    pub fn open(&mut self, code: &str) {
+        self.statement(code);
+        self.depth += 1;
+    }
+
+    /// Appends statement that closes a block, decreasing indentation.
+    pub fn close(&mut self, code: &str) {
+        self.depth = self.depth.saturating_sub(1);
+        self.statement(code);
+    }
+
+    /// Generated code, without a trailing newline.
+    pub fn finish(mut self) -> String {
+        if !self.minify {
+            self.lines.pop();
+        }
+        self.lines.join("\n")
+    }
+}
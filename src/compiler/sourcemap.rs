@@ -0,0 +1,176 @@
+use crate::{parser::ast::{ASTNode, BoxedASTNode}, util::json::quote};
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Settings for source map generation.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMapOptions {
+    /// Name of the compiled javascript file.
+    pub file: String,
+    /// Name of the hasty source file, as it should appear in the browser.
+    pub source_name: String,
+    /// Content of the hasty source file, needed to compute columns.
+    pub source: String,
+    /// Url of the source map, appended as `//# sourceMappingURL` comment.
+    pub url: String,
+}
+
+/// Single mapping from generated position to source position (all 0-based).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub generated_line: usize,
+    pub generated_column: usize,
+    pub source_line: usize,
+    pub source_column: usize,
+    /// Index of the original name in `SourceMap::names`.
+    pub name: Option<usize>,
+}
+
+/// Source Map v3 of a compiled module.
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    pub file: String,
+    pub source_name: String,
+    pub source: String,
+    /// Original names of renamed identifiers.
+    pub names: Vec<String>,
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub fn new(options: &SourceMapOptions) -> Self {
+        Self {
+            file: options.file.clone(),
+            source_name: options.source_name.clone(),
+            source: options.source.clone(),
+            names: Vec::new(),
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Maps every line of a generated statement to the place where its node starts.
+    /// Positions inside of a single statement are not tracked.
+    pub fn map_statement(&mut self, (generated_line, generated_column): (usize, usize), generated: &str, node: &BoxedASTNode) {
+        let (source_line, source_column) = self.line_and_column(Self::start_offset(&node.borrow()));
+        for line in 0..generated.lines().count().max(1) {
+            self.mappings.push(Mapping {
+                generated_line: generated_line + line,
+                generated_column,
+                source_line,
+                source_column,
+                name: None,
+            });
+        }
+    }
+
+    /// Maps identifier in generated code to its original name.
+    pub fn map_name(&mut self, (generated_line, generated_column): (usize, usize), source_offset: usize, name: &str) {
+        let (source_line, source_column) = self.line_and_column(source_offset);
+        let index = match self.names.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            },
+        };
+
+        self.mappings.push(Mapping {
+            generated_line,
+            generated_column,
+            source_line,
+            source_column,
+            name: Some(index),
+        });
+    }
+
+    /// Offset of the leftmost token of a node.
+    fn start_offset(node: &ASTNode) -> usize {
+        match node {
+            ASTNode::Binary { left, .. } | ASTNode::Logical { left, .. } => Self::start_offset(&left.borrow()),
+            ASTNode::Grouping { expr } => Self::start_offset(&expr.borrow()),
+            _ => node.position().start,
+        }
+    }
+
+    /// Converts byte offset in source into line and column (in characters).
+    fn line_and_column(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let before = &self.source[..offset];
+        let line = before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count())
+    }
+
+    /// Encodes mappings in the base64 VLQ format.
+    pub fn encode_mappings(&self) -> String {
+        let mut result = String::new();
+        let mut line = 0;
+        let (mut previous_column, mut previous_source_line, mut previous_source_column) = (0, 0, 0);
+        let mut previous_name = 0;
+
+        for (i, mapping) in self.mappings.iter().enumerate() {
+            if i > 0 && mapping.generated_line == line {
+                result.push(',');
+            }
+            while line < mapping.generated_line {
+                result.push(';');
+                line += 1;
+                previous_column = 0;
+            }
+
+            Self::encode_vlq(&mut result, mapping.generated_column as i64 - previous_column);
+            // There is only ever one source file.
+            Self::encode_vlq(&mut result, 0);
+            Self::encode_vlq(&mut result, mapping.source_line as i64 - previous_source_line);
+            Self::encode_vlq(&mut result, mapping.source_column as i64 - previous_source_column);
+            if let Some(name) = mapping.name {
+                Self::encode_vlq(&mut result, name as i64 - previous_name);
+                previous_name = name as i64;
+            }
+
+            previous_column = mapping.generated_column as i64;
+            previous_source_line = mapping.source_line as i64;
+            previous_source_column = mapping.source_column as i64;
+        }
+
+        result
+    }
+
+    fn encode_vlq(target: &mut String, value: i64) {
+        let mut value = if value < 0 { ((-value) << 1) | 1 } else { value << 1 };
+        loop {
+            let mut digit = value & 0b11111;
+            value >>= 5;
+            if value > 0 { digit |= 0b100000; }
+            target.push(BASE64[digit as usize] as char);
+            if value == 0 { break; }
+        }
+    }
+
+    /// Serializes source map into JSON.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"version\":3,\"file\":{},\"sources\":[{}],\"sourcesContent\":[{}],\"names\":[{}],\"mappings\":{}}}",
+            quote(&self.file),
+            quote(&self.source_name),
+            quote(&self.source),
+            self.names.iter().map(|n| quote(n)).collect::<Vec<_>>().join(","),
+            quote(&self.encode_mappings()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vlq() {
+        let mut result = String::new();
+        for value in [0, 1, -1, 15, 16, -17, 1000] {
+            SourceMap::encode_vlq(&mut result, value);
+            result.push(' ');
+        }
+        assert_eq!(result, "A C D e gB jB w+B ");
+    }
+}
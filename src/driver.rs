@@ -0,0 +1,166 @@
+use std::fmt::{Debug, Display};
+
+use crate::{
+    compiler::{sourcemap::SourceMap, Compiler, CompilerOptions},
+    parser::{ast::BoxedASTNode, Parser},
+    passes::{
+        constant_folding::ConstantFolder,
+        dead_code::{DeadCodeEliminator, DeadCodeWarning, DeadCodeWarningTy},
+        lint::{LintConfig, LintReport, Linter},
+        manager::PassManager,
+        resolver::Resolver,
+        typecheck::TypeChecker,
+    },
+    scanner::Scanner,
+    util::{diagnostic::{Diagnostic, Severity}, json::Json, HastyError},
+};
+
+/// Errors and warnings reported while compiling a program.
+#[derive(Default)]
+pub struct Diagnostics {
+    pub errors: Vec<Box<dyn HastyError>>,
+    pub warnings: Vec<Box<dyn HastyError>>,
+}
+
+impl Diagnostics {
+    pub(crate) fn error(error: impl HastyError + 'static) -> Self {
+        Self { errors: vec![Box::new(error)], warnings: Vec::new() }
+    }
+
+    /// Every warning and error as structured diagnostics, warnings first.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.warnings.iter().chain(self.errors.iter()).map(|d| d.diagnostic()).collect()
+    }
+
+    /// Every warning and error as a JSON array, see `Diagnostic::to_json`.
+    pub fn to_json(&self, source: &str, file: Option<&str>) -> Json {
+        self.diagnostics().iter().map(|d| d.to_json(source, file)).collect::<Vec<_>>().into()
+    }
+
+    /// Every warning and error with the lines of `source` they point at.
+    pub fn render(&self, source: &str) -> String {
+        self.diagnostics().iter().map(|d| d.render(source)).collect::<Vec<_>>().join("\n")
+    }
+}
+
+impl Display for Diagnostics {
+    /// Every warning and error in the unified form, without source lines (see `render`).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for diagnostic in self.warnings.iter().chain(self.errors.iter()) {
+            writeln!(f, "{}", diagnostic.as_hasty_error_string(""))?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let describe = |diagnostics: &Vec<Box<dyn HastyError>>| {
+            diagnostics.iter().map(|d| d.get_error_description()).collect::<Vec<_>>()
+        };
+        f.debug_struct("Diagnostics")
+            .field("errors", &describe(&self.errors))
+            .field("warnings", &describe(&self.warnings))
+            .finish()
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Program that was parsed and went through all passes.
+pub struct Analysis {
+    pub ast: Vec<BoxedASTNode>,
+    pub warnings: Vec<Box<dyn HastyError>>,
+}
+
+/// Compiled javascript module.
+pub struct Output {
+    pub code: String,
+    /// Present if it was enabled in options.
+    pub source_map: Option<SourceMap>,
+    pub warnings: Vec<Box<dyn HastyError>>,
+}
+
+/// Scans and parses the program, then resolves, type checks, lints and optimizes it.
+/// Lints have their default levels.
+pub fn analyze(source: &str) -> Result<Analysis, Diagnostics> {
+    analyze_with(source, &LintConfig::new())
+}
+
+/// Same as `analyze`, with lint levels from `lints`.
+pub fn analyze_with(source: &str, lints: &LintConfig) -> Result<Analysis, Diagnostics> {
+    let (tokens, comments) = Scanner::new(source).scan_with_comments().map_err(Diagnostics::error)?;
+    let mut ast = Parser::new(tokens).parse().map_err(Diagnostics::error)?;
+
+    let mut manager = PassManager::new();
+    manager
+        .register(Resolver::new())
+        .register(TypeChecker::new())
+        .register(Linter::new(lints.clone()).with_comments(comments))
+        .register(ConstantFolder::new())
+        .register(DeadCodeEliminator::new());
+
+    let result = manager.run(&mut ast);
+    let mut warnings: Vec<Box<dyn HastyError>> = manager.result::<Vec<LintReport>>()
+        .map(|reports| reports.iter().map(|r| Box::new(r.clone()) as Box<dyn HastyError>).collect())
+        .unwrap_or_default();
+    // Unused variables are reported by the configurable `unused-variable` lint instead.
+    warnings.extend(manager.result::<Vec<DeadCodeWarning>>().into_iter().flat_map(|warnings| {
+        warnings.iter()
+            .filter(|w| !matches!(w.ty, DeadCodeWarningTy::UnusedVariable))
+            .map(|w| Box::new(w.clone()) as Box<dyn HastyError>)
+            .collect::<Vec<_>>()
+    }));
+
+    match result {
+        Ok(()) => Ok(Analysis { ast, warnings }),
+        Err(errors) => {
+            // Linter fails with every report if any lint is denied, not only with the errors.
+            let (errors, lints): (Vec<_>, Vec<_>) = errors.into_iter()
+                .partition(|error| error.diagnostic().severity == Severity::Error);
+            warnings.extend(lints);
+            Err(Diagnostics { errors, warnings })
+        },
+    }
+}
+
+/// Compiles program to a javascript module.
+pub fn compile(source: &str, options: CompilerOptions) -> Result<Output, Diagnostics> {
+    compile_with(source, options, &LintConfig::new())
+}
+
+/// Same as `compile`, with lint levels from `lints`.
+pub fn compile_with(source: &str, options: CompilerOptions, lints: &LintConfig) -> Result<Output, Diagnostics> {
+    let Analysis { ast, warnings } = analyze_with(source, lints)?;
+
+    let mut code = Vec::new();
+    let source_map = match Compiler::with_options(options).compile_ast(&ast, &mut code) {
+        Ok(source_map) => source_map,
+        Err(error) => return Err(Diagnostics { errors: vec![Box::new(error)], warnings }),
+    };
+
+    Ok(Output {
+        code: String::from_utf8(code).unwrap(),
+        source_map,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile() {
+        let output = compile("pub const x = 1 + 2; let y = 1;", CompilerOptions::default()).unwrap();
+        assert_eq!(output.code, "const x = 3;\nexport { x };\n");
+        assert!(output.source_map.is_none());
+        assert_eq!(output.warnings.len(), 1);
+
+        let Err(diagnostics) = compile("let x: int = 1.5; y", CompilerOptions::default()) else { panic!() };
+        assert_eq!(diagnostics.errors.len(), 1);
+        assert_eq!(diagnostics.errors[0].get_error_description(), "Undefined variable 'y'.");
+
+        assert!(compile("1 +", CompilerOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{
+    parser::{ast::{ASTNode, BoxedASTNode}, Parser},
+    scanner::{Scanner, Token},
+    Diagnostics,
+};
+
+/// Options that change how the code is formatted.
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Maximum length of a line. Longer expressions are broken before operators.
+    pub width: usize,
+    /// String used for a single level of indentation.
+    pub indent: String,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            width: 100,
+            indent: "    ".to_string(),
+        }
+    }
+}
+
+/// Pretty-prints source code in the canonical style.
+///
+/// Every statement is written on its own line, with at most one blank line kept
+/// between them. Comments are kept: comments on the same line as the end of a
+/// statement stay after it, all others are written on their own line before
+/// the next statement. Formatting already formatted code does not change it.
+pub struct Formatter {
+    options: FormatOptions,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Formatter {
+    pub fn new() -> Self {
+        Self::with_options(FormatOptions::default())
+    }
+
+    pub fn with_options(options: FormatOptions) -> Self {
+        Self { options }
+    }
+
+    /// Formats the program, which has to be scanned and parsed without errors.
+    pub fn format(&self, source: &str) -> Result<String, Diagnostics> {
+        let (tokens, comments) = Scanner::new(source).scan_with_comments().map_err(Diagnostics::error)?;
+        let ast = Parser::new(tokens).parse().map_err(Diagnostics::error)?;
+
+        let mut layout = Layout {
+            output: String::new(),
+            source,
+            comments: comments.into_iter().peekable(),
+            previous: None,
+        };
+        self.block(&mut layout, &ast, None, 0);
+        layout.comments_before(source.len(), "");
+
+        let mut output = layout.output;
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        Ok(output)
+    }
+
+    /// Writes statements, each on its own line indented `depth` levels, with comments placed among them.
+    /// `limit` is the offset at which the block ends, if it is not the end of source.
+    fn block(&self, layout: &mut Layout, nodes: &[BoxedASTNode], limit: Option<usize>, depth: usize) {
+        let indent = self.options.indent.repeat(depth);
+
+        for (i, node) in nodes.iter().enumerate() {
+            let (start, mut end) = Self::span(node);
+            // Comments inside of a statement can not be kept in place, so they are moved before it.
+            // Only comments in the header of a test are moved, comments in its body keep their place.
+            let header = match **node.borrow() {
+                ASTNode::Test { ref name, .. } => name.start + name.lexeme.len(),
+                _ => end,
+            };
+            layout.comments_before(header, &indent);
+
+            layout.separate(start);
+            layout.output.push_str(&indent);
+            match **node.borrow() {
+                ASTNode::Test { ref name, ref body, end: ref brace } => {
+                    layout.output.push_str(&format!("test fn {}() {{", name.lexeme));
+                    if body.is_empty() && layout.comments.peek().is_none_or(|comment| comment.start > brace.start) {
+                        layout.output.push('}');
+                    } else {
+                        layout.output.push('\n');
+                        layout.previous = None;
+                        self.block(layout, body, Some(brace.start), depth + 1);
+                        layout.comments_before(brace.start, &self.options.indent.repeat(depth + 1));
+                        layout.output.push('\n');
+                        layout.output.push_str(&indent);
+                        layout.output.push('}');
+                    }
+                },
+                _ => layout.output.push_str(&self.statement(node, depth)),
+            }
+
+            let next = nodes.get(i + 1).map(|node| Self::span(node).0).or(limit);
+            if let Some(comment) = layout.comments.next_if(|comment| {
+                !layout.source[end..comment.start].contains('\n') && next.is_none_or(|next| comment.start < next)
+            }) {
+                layout.output.push(' ');
+                layout.output.push_str(comment.lexeme.trim_end());
+                end = comment.start + comment.lexeme.len();
+            }
+            layout.previous = Some(end);
+        }
+    }
+
+    fn statement(&self, node: &BoxedASTNode, depth: usize) -> String {
+        let column = self.options.indent.chars().count() * depth;
+        let n = node.borrow();
+        let ASTNode::VarDecl { ref name, ref ty, ref initializer, constant, public } = **n else {
+            return self.expression(node, column, depth);
+        };
+
+        let mut code = String::new();
+        if public { code.push_str("pub "); }
+        code.push_str(if constant { "const " } else { "let " });
+        code.push_str(&name.lexeme);
+        if let Some(ty) = ty {
+            code.push_str(": ");
+            code.push_str(&ty.lexeme);
+        }
+        if let Some(initializer) = initializer {
+            code.push_str(" = ");
+            // Reserve space for the semicolon.
+            let initializer = self.expression(initializer, column + code.chars().count() + 1, depth);
+            code.push_str(&initializer);
+        }
+        code.push(';');
+        code
+    }
+
+    /// Expression starting at `column`, broken into lines if it does not fit.
+    /// Continuation lines are indented one level deeper than `depth`.
+    fn expression(&self, node: &BoxedASTNode, column: usize, depth: usize) -> String {
+        let flat = Self::flat(node);
+        if column + flat.chars().count() <= self.options.width {
+            return flat;
+        }
+
+        match **node.borrow() {
+            ASTNode::Binary { .. } | ASTNode::Logical { .. } => {
+                let (first, rest) = Self::chain(node);
+                let indent = self.options.indent.repeat(depth + 1);
+
+                let mut code = self.expression(&first, column, depth + 1);
+                for (operator, operand) in rest {
+                    let column = indent.chars().count() + operator.lexeme.len() + 1;
+                    code.push_str(&format!("\n{}{} {}", indent, operator.lexeme, self.expression(&operand, column, depth + 1)));
+                }
+                code
+            },
+            ASTNode::Unary { ref operator, ref right } => {
+                Self::prefix(operator, self.expression(right, column + operator.lexeme.len(), depth))
+            },
+            ASTNode::Grouping { ref expr } => format!("({})", self.expression(expr, column + 1, depth)),
+            ASTNode::Assert { ref keyword, ref condition, ref message } => {
+                let column = column + keyword.lexeme.len() + 1;
+                let condition = self.expression(condition, column, depth);
+                match message {
+                    Some(message) => {
+                        let column = match condition.rsplit_once('\n') {
+                            Some((_, last)) => last.chars().count() + 2,
+                            None => column + condition.chars().count() + 2,
+                        };
+                        format!("{}({}, {})", keyword.lexeme, condition, self.expression(message, column, depth))
+                    },
+                    None => format!("{}({})", keyword.lexeme, condition),
+                }
+            },
+            _ => flat,
+        }
+    }
+
+    /// Expression written on a single line.
+    fn flat(node: &BoxedASTNode) -> String {
+        match **node.borrow() {
+            ASTNode::Binary { ref left, ref operator, ref right }
+            | ASTNode::Logical { ref left, ref operator, ref right } => {
+                format!("{} {} {}", Self::flat(left), operator.lexeme, Self::flat(right))
+            },
+            ASTNode::Unary { ref operator, ref right } => Self::prefix(operator, Self::flat(right)),
+            ASTNode::Literal { ref value } => value.lexeme.clone(),
+            ASTNode::Grouping { ref expr } => format!("({})", Self::flat(expr)),
+            ASTNode::Variable { ref name } => name.lexeme.clone(),
+            ASTNode::Assert { ref keyword, ref condition, ref message } => match message {
+                Some(message) => format!("{}({}, {})", keyword.lexeme, Self::flat(condition), Self::flat(message)),
+                None => format!("{}({})", keyword.lexeme, Self::flat(condition)),
+            },
+            ASTNode::VarDecl { .. } | ASTNode::Test { .. } => unreachable!("declarations are not expressions"),
+        }
+    }
+
+    /// Prefix operator with its operand, separated if they would merge into `--`.
+    fn prefix(operator: &Token, operand: String) -> String {
+        if operand.starts_with(&operator.lexeme) {
+            format!("{} {}", operator.lexeme, operand)
+        } else {
+            format!("{}{}", operator.lexeme, operand)
+        }
+    }
+
+    /// Splits left associative chain of operators with the same precedence,
+    /// like `a + b - c`, into the first operand and the following operations.
+    fn chain(node: &BoxedASTNode) -> (BoxedASTNode, Vec<(Token, BoxedASTNode)>) {
+        let mut rest = Vec::new();
+        let mut current = node.clone();
+        let precedence = Self::precedence(node);
+
+        loop {
+            let next = match **current.borrow() {
+                ASTNode::Binary { ref left, ref operator, ref right }
+                | ASTNode::Logical { ref left, ref operator, ref right } => {
+                    rest.push((operator.clone(), right.clone()));
+                    left.clone()
+                },
+                _ => break,
+            };
+            current = next;
+            if Self::precedence(&current) != precedence { break; }
+        }
+
+        rest.reverse();
+        (current, rest)
+    }
+
+    /// Precedence of a binary operator, `None` for other nodes.
+    fn precedence(node: &BoxedASTNode) -> Option<u8> {
+        use crate::scanner::TokenType::*;
+
+        match **node.borrow() {
+            ASTNode::Binary { ref operator, .. } | ASTNode::Logical { ref operator, .. } => Some(match operator.token_type {
+                OR => 0,
+                AND => 1,
+                EQUAL_EQUAL | BANG_EQUAL => 2,
+                LESS | LESS_EQUAL | GREATER | GREATER_EQUAL => 3,
+                PLUS | MINUS => 4,
+                _ => 5,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Offsets of the first and after the last token of a node.
+    fn span(node: &BoxedASTNode) -> (usize, usize) {
+        let span = node.borrow().span();
+        (span.start, span.end)
+    }
+}
+
+/// Formatted code written so far, with comments that were not written yet.
+struct Layout<'a> {
+    output: String,
+    source: &'a str,
+    comments: std::iter::Peekable<std::vec::IntoIter<Token>>,
+    /// Offset after the last written code, `None` at the start of a line.
+    previous: Option<usize>,
+}
+
+impl Layout<'_> {
+    /// Starts a new line, keeping a single blank line if there was any in the source.
+    fn separate(&mut self, next: usize) {
+        let Some(previous) = self.previous else { return };
+        self.output.push('\n');
+        if self.source.get(previous..next).unwrap_or("").matches('\n').count() > 1 {
+            self.output.push('\n');
+        }
+    }
+
+    /// Writes comments starting before `offset`, each on its own line.
+    fn comments_before(&mut self, offset: usize, indent: &str) {
+        while let Some(comment) = self.comments.next_if(|comment| comment.start < offset) {
+            self.separate(comment.start);
+            self.output.push_str(indent);
+            self.output.push_str(comment.lexeme.trim_end());
+            self.previous = Some(comment.start + comment.lexeme.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn format(code: &str, width: usize) -> String {
+        let formatter = Formatter::with_options(FormatOptions { width, ..Default::default() });
+        let formatted = formatter.format(code).unwrap();
+        assert_eq!(formatter.format(&formatted).unwrap(), formatted, "formatting is not idempotent");
+        formatted
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            format("// header\npub   const x:int=1+2*(3-4) ;   // trailing  \n\n\n\nlet y=- -x;x  !true // end\n// last", 100),
+            "// header\npub const x: int = 1 + 2 * (3 - 4); // trailing\n\nlet y = - -x;\nx\n!true // end\n// last\n"
+        );
+        assert_eq!(format("let a = 1 + // inside\n 2;", 100), "// inside\nlet a = 1 + 2;\n");
+        assert_eq!(format("", 100), "");
+        assert_eq!(
+            format("test  fn works ( ) {\n// setup\nlet x=1;\n\n\n  assert( x==1 ,\"one\") // check\n// done\n} // after\ntest fn empty(){ }", 100),
+            "test fn works() {\n    // setup\n    let x = 1;\n\n    assert(x == 1, \"one\") // check\n    // done\n} // after\ntest fn empty() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_long_lines() {
+        assert_eq!(
+            format("let total = first * second + third - fourth * fifth;", 30),
+            "let total = first * second\n    + third\n    - fourth * fifth;\n"
+        );
+        assert_eq!(
+            format("alpha && (beta || gamma || delta) && epsilon", 24),
+            "alpha\n    && (beta\n        || gamma\n        || delta)\n    && epsilon\n"
+        );
+    }
+}
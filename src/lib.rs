@@ -1,14 +1,28 @@
 #![allow(dead_code)]
 
-mod scanner;
-mod util;
-mod parser;
-mod passes;
-mod compiler;
-
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
-}
+//! Compiler for the hasty language.
+//!
+//! `compile` runs the whole pipeline, from source code to javascript.
+//! Each stage is also available on its own through the modules below.
+
+pub mod scanner;
+pub mod util;
+pub mod parser;
+pub mod passes;
+pub mod compiler;
+pub mod backend;
+pub mod formatter;
+pub mod lsp;
+pub mod repl;
+pub mod test_runner;
+mod driver;
+
+pub use driver::{analyze, analyze_with, compile, compile_with, Analysis, Diagnostics, Output};
+pub use scanner::{Token, TokenType};
+pub use parser::ast::{ASTNode, BoxedASTNode};
+pub use passes::Pass;
+pub use compiler::{Compiler, CompilerOptions};
+pub use util::diagnostic::{Diagnostic, Severity, Span};
 
 #[cfg(test)]
 mod tests {
@@ -39,6 +53,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_const_var_decl() {
+        assert_eq!(
+            parse("const x = 1;"),
+            "{\n    (constvardecl x = 1)\n}\n"
+        );
+    }
+
     #[test]
     fn test_var_get() {
         assert_eq!(
@@ -46,4 +68,12 @@ mod tests {
             "{\n    (+ 1 (var x))\n}\n"
         );
     }
+
+    #[test]
+    fn test_test_decl() {
+        assert_eq!(
+            parse("test fn works() { let x = 1; assert(x == 1, \"one\") } test"),
+            "{\n    (test works (letvardecl x = 1) (assert (== (var x) 1) \"one\"))\n    (var test)\n}\n"
+        );
+    }
 }
@@ -0,0 +1,602 @@
+use std::{collections::HashMap, io::{BufRead, Write}};
+
+use url::Url;
+
+use crate::{
+    parser::{ast::{ASTNode, BoxedASTNode}, Parser},
+    passes::{lint::LintConfig, resolver::{BindingId, Resolver}, typecheck::{Type, TypeChecker}, Pass},
+    scanner::{Scanner, TokenType},
+    util::{diagnostic::{Severity, Span}, json::Json, scope::Scope},
+};
+
+/// Keywords offered by completion.
+const KEYWORDS: &[&str] = &["let", "const", "pub", "true", "false", "nil", "test", "assert"];
+
+// JSON-RPC error codes.
+const PARSE_ERROR: f64 = -32700.0;
+const INVALID_REQUEST: f64 = -32600.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+const INVALID_PARAMS: f64 = -32602.0;
+
+// LSP enumerations.
+const SEVERITY_ERROR: usize = 1;
+const SEVERITY_WARNING: usize = 2;
+const SEVERITY_INFORMATION: usize = 3;
+const SYMBOL_FUNCTION: usize = 12;
+const SYMBOL_VARIABLE: usize = 13;
+const SYMBOL_CONSTANT: usize = 14;
+const COMPLETION_VARIABLE: usize = 6;
+const COMPLETION_KEYWORD: usize = 14;
+
+/// Reads body of a single message framed with `Content-Length` header.
+/// Returns `None` at the end of input.
+pub fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() { break; }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+    let length = length.ok_or_else(|| invalid("Missing Content-Length header."))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body).map(Some).map_err(|_| invalid("Message is not valid UTF-8."))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Json) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// Runs language server until `exit` notification or the end of input.
+pub fn run(mut input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    let mut server = Server::new();
+    while let Some(body) = read_message(&mut input)? {
+        let replies = match Json::parse(&body) {
+            Ok(message) => server.handle(&message),
+            Err(error) => vec![Server::error(Json::Null, PARSE_ERROR, error.message.to_string())],
+        };
+
+        for reply in replies {
+            write_message(&mut output, &reply)?;
+        }
+        if server.exited { break; }
+    }
+    Ok(())
+}
+
+/// Open document with offsets of its lines.
+struct Document {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    /// LSP position of a byte offset. Characters are counted in UTF-16 code units.
+    fn position(&self, offset: usize) -> Json {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let character = self.text[self.line_starts[line]..offset].encode_utf16().count();
+        Json::object([("line", line.into()), ("character", character.into())])
+    }
+
+    /// Byte offset of an LSP position.
+    fn offset(&self, position: &Json) -> Option<usize> {
+        let line = position.get("line")?.as_usize()?;
+        let character = position.get("character")?.as_usize()?;
+        let start = *self.line_starts.get(line)?;
+
+        let mut units = 0;
+        for (i, c) in self.text[start..].char_indices() {
+            if units >= character || c == '\n' {
+                return Some(start + i);
+            }
+            units += c.len_utf16();
+        }
+        Some(self.text.len())
+    }
+
+    fn range(&self, span: impl Into<Span>) -> Json {
+        let span = span.into();
+        Json::object([("start", self.position(span.start)), ("end", self.position(span.end))])
+    }
+
+    /// Parses, resolves and type checks the document. Passes run even if earlier
+    /// ones fail, so that as much metadata as possible is available.
+    fn analyze(&self) -> Option<Vec<BoxedASTNode>> {
+        let tokens = Scanner::new(&self.text).scan().ok()?;
+        let mut ast = Parser::new(tokens).parse().ok()?;
+        let _ = Resolver::new().process(&mut ast);
+        let _ = TypeChecker::new().process(&mut ast);
+        Some(ast)
+    }
+}
+
+/// Language server, handling one JSON-RPC message at a time.
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    exited: bool,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            shutdown: false,
+            exited: false,
+        }
+    }
+
+    /// Handles a request or notification, returning messages to send back.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+
+        let Some(id) = message.get("id").cloned() else {
+            // After shutdown, only `exit` is expected.
+            if self.shutdown && method != "exit" { return Vec::new(); }
+            return self.notification(method, params);
+        };
+        if self.shutdown {
+            return vec![Self::error(id, INVALID_REQUEST, "Server is shutting down.".to_string())];
+        }
+
+        let result = match method {
+            "initialize" => Ok(Self::capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            },
+            "textDocument/hover" => self.with_document(params, Self::hover),
+            "textDocument/definition" => self.with_document(params, Self::definition),
+            "textDocument/documentSymbol" => self.with_document(params, Self::symbols),
+            "textDocument/completion" => self.with_document(params, Self::completion),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'.", method))),
+        };
+
+        vec![match result {
+            Ok(result) => Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)]),
+            Err((code, message)) => Self::error(id, code, message),
+        }]
+    }
+
+    /// Whether `exit` notification was received.
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = params.path(&["textDocument", "uri"]).and_then(Json::as_str).unwrap_or("").to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params.path(&["textDocument", "text"]).and_then(Json::as_str).unwrap_or("");
+                self.documents.insert(uri.clone(), Document::new(text.to_string()));
+                vec![self.diagnostics(&uri)]
+            },
+            "textDocument/didChange" => {
+                // Only full document sync is supported, so the last change is the whole text.
+                let changes = params.get("contentChanges").and_then(Json::as_array);
+                let Some(text) = changes.and_then(|c| c.last()).and_then(|c| c.get("text")).and_then(Json::as_str) else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.clone(), Document::new(text.to_string()));
+                vec![self.diagnostics(&uri)]
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                vec![Self::publish(&uri, Vec::new())]
+            },
+            "exit" => {
+                self.exited = true;
+                Vec::new()
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    fn capabilities() -> Json {
+        Json::object([
+            ("capabilities", Json::object([
+                ("textDocumentSync", 1.into()),
+                ("hoverProvider", true.into()),
+                ("definitionProvider", true.into()),
+                ("documentSymbolProvider", true.into()),
+                ("completionProvider", Json::Object(Vec::new())),
+            ])),
+            ("serverInfo", Json::object([("name", "hasty".into())])),
+        ])
+    }
+
+    fn with_document(
+        &self, params: &Json,
+        handler: impl FnOnce(&Document, &str, &Json) -> Json,
+    ) -> Result<Json, (f64, String)> {
+        let uri = params.path(&["textDocument", "uri"]).and_then(Json::as_str)
+            .ok_or((INVALID_PARAMS, "Missing document uri.".to_string()))?;
+        let document = self.documents.get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Document '{}' is not open.", uri)))?;
+        Ok(handler(document, uri, params))
+    }
+
+    /// Errors and warnings of the whole pipeline.
+    fn diagnostics(&self, uri: &str) -> Json {
+        let Some(document) = self.documents.get(uri) else { return Self::publish(uri, Vec::new()) };
+        let diagnostics = match crate::analyze_with(&document.text, &Self::lint_config(uri)) {
+            Ok(analysis) => analysis.warnings.iter().map(|warning| warning.diagnostic()).collect(),
+            Err(diagnostics) => diagnostics.diagnostics(),
+        };
+
+        let diagnostics = diagnostics.into_iter().map(|diagnostic| {
+            let severity = match diagnostic.severity {
+                Severity::Error => SEVERITY_ERROR,
+                Severity::Warning => SEVERITY_WARNING,
+                Severity::Note => SEVERITY_INFORMATION,
+            };
+            Json::object([
+                ("range", document.range(diagnostic.primary_span().unwrap_or(Span::new(0, 0)))),
+                ("severity", severity.into()),
+                ("code", diagnostic.code.unwrap_or_default().into()),
+                ("source", "hasty".into()),
+                ("message", diagnostic.message.into()),
+            ])
+        }).collect();
+        Self::publish(uri, diagnostics)
+    }
+
+    /// Lint levels from the project file (`hasty.toml`) of a document, same as in `hasty check`.
+    /// Default levels are used if there is no such file or it is invalid.
+    fn lint_config(uri: &str) -> LintConfig {
+        let Some(path) = Url::parse(uri).ok().and_then(|url| url.to_file_path().ok()) else { return LintConfig::new() };
+        path.parent()
+            .and_then(LintConfig::find_project_file)
+            .and_then(|file| std::fs::read_to_string(file).ok())
+            .and_then(|text| LintConfig::parse(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            ("params", Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())])),
+        ])
+    }
+
+    /// Inferred type of the expression under the cursor.
+    fn hover(document: &Document, _uri: &str, params: &Json) -> Json {
+        let Some((_, node)) = Self::node_at_position(document, params) else { return Json::Null };
+        let Some(ty) = node.meta.get::<Type>() else { return Json::Null };
+
+        let n = node.borrow();
+        let value = match **n {
+            ASTNode::Variable { ref name } | ASTNode::VarDecl { ref name, .. } => format!("{}: {}", name.lexeme, ty),
+            _ => ty.to_string(),
+        };
+        Json::object([
+            ("contents", Json::object([("kind", "plaintext".into()), ("value", value.into())])),
+            ("range", document.range(&n.position())),
+        ])
+    }
+
+    /// Declaration of the variable under the cursor, found by its binding.
+    fn definition(document: &Document, uri: &str, params: &Json) -> Json {
+        let Some((ast, node)) = Self::node_at_position(document, params) else { return Json::Null };
+        let Some(id) = node.meta.get::<BindingId>() else { return Json::Null };
+
+        let declaration = Self::declaration(&ast, *id);
+        match declaration.map(|node| node.borrow().position()) {
+            Some(name) => Json::object([("uri", uri.into()), ("range", document.range(&name))]),
+            None => Json::Null,
+        }
+    }
+
+    fn symbols(document: &Document, _uri: &str, _params: &Json) -> Json {
+        let Some(ast) = document.analyze() else { return Json::Array(Vec::new()) };
+        ast.iter().filter_map(|node| {
+            let n = node.borrow();
+            let (name, detail, kind) = match **n {
+                ASTNode::VarDecl { ref name, constant, .. } => {
+                    let detail = node.meta.get::<Type>().map(|ty| ty.to_string()).unwrap_or_default();
+                    (name, detail, if constant { SYMBOL_CONSTANT } else { SYMBOL_VARIABLE })
+                },
+                ASTNode::Test { ref name, .. } => (name, "test".to_string(), SYMBOL_FUNCTION),
+                _ => return None,
+            };
+            Some(Json::object([
+                ("name", name.lexeme.as_str().into()),
+                ("detail", detail.into()),
+                ("kind", kind.into()),
+                ("range", document.range(name)),
+                ("selectionRange", document.range(name)),
+            ]))
+        }).collect::<Vec<_>>().into()
+    }
+
+    /// Names declared before the cursor, with keywords.
+    /// Tests run after the module, so inside of a test all module declarations are visible.
+    fn completion(document: &Document, _uri: &str, params: &Json) -> Json {
+        let offset = params.get("position").and_then(|p| document.offset(p)).unwrap_or(0);
+        let scope: Scope<Option<Type>> = Scope::new();
+
+        match document.analyze() {
+            Some(ast) => {
+                let test = ast.iter().find(|node| {
+                    let span = node.borrow().span();
+                    node.borrow().is_test() && span.start <= offset && offset <= span.end
+                });
+                let visible = |node: &&BoxedASTNode| test.is_some() || Self::end(node) <= offset;
+                let body = match test.map(Self::children) {
+                    Some(body) => body.into_iter().filter(|node| Self::end(node) <= offset).collect(),
+                    None => Vec::new(),
+                };
+
+                for node in ast.iter().filter(visible).chain(&body) {
+                    if let ASTNode::VarDecl { ref name, .. } = **node.borrow() {
+                        scope.insert(&name.lexeme, node.meta.get::<Type>().map(|ty| *ty));
+                    }
+                }
+            },
+            // Program does not parse while it is being edited, so names are taken from tokens.
+            None => {
+                let tokens = Scanner::new(&document.text[..offset]).scan().unwrap_or_default();
+                for pair in tokens.windows(2) {
+                    if matches!(pair[0].token_type, TokenType::LET | TokenType::CONST) && pair[1].token_type == TokenType::IDENTIFIER {
+                        scope.insert(&pair[1].lexeme, None);
+                    }
+                }
+            },
+        }
+
+        let variables = scope.names().into_iter().map(|name| {
+            let detail = scope.get(&name).flatten().map(|ty| ty.to_string()).unwrap_or_default();
+            Json::object([("label", name.into()), ("kind", COMPLETION_VARIABLE.into()), ("detail", detail.into())])
+        });
+        let keywords = KEYWORDS.iter().map(|keyword| {
+            Json::object([("label", (*keyword).into()), ("kind", COMPLETION_KEYWORD.into())])
+        });
+        variables.chain(keywords).collect::<Vec<_>>().into()
+    }
+
+    /// Analyzed program and the innermost node under the cursor.
+    fn node_at_position(document: &Document, params: &Json) -> Option<(Vec<BoxedASTNode>, BoxedASTNode)> {
+        let offset = document.offset(params.get("position")?)?;
+        let ast = document.analyze()?;
+        let node = Self::node_at(&ast, offset)?;
+        Some((ast, node))
+    }
+
+    /// Declaration of a binding, searched in all nested nodes.
+    fn declaration(nodes: &[BoxedASTNode], id: BindingId) -> Option<BoxedASTNode> {
+        nodes.iter().find_map(|node| {
+            if node.meta.get::<BindingId>().as_deref() == Some(&id) && matches!(**node.borrow(), ASTNode::VarDecl { .. }) {
+                return Some(node.clone());
+            }
+            Self::declaration(&Self::children(node), id)
+        })
+    }
+
+    fn node_at(nodes: &[BoxedASTNode], offset: usize) -> Option<BoxedASTNode> {
+        nodes.iter().find_map(|node| {
+            if let Some(child) = Self::node_at(&Self::children(node), offset) {
+                return Some(child);
+            }
+            let token = node.borrow().position();
+            (token.start <= offset && offset <= token.start + token.lexeme.len()).then(|| node.clone())
+        })
+    }
+
+    /// Offset just after the last token of a node.
+    fn end(node: &BoxedASTNode) -> usize {
+        let token = node.borrow().position();
+        Self::children(node).iter()
+            .map(Self::end)
+            .fold(token.start + token.lexeme.len(), usize::max)
+    }
+
+    fn children(node: &BoxedASTNode) -> Vec<BoxedASTNode> {
+        match **node.borrow() {
+            ASTNode::Binary { ref left, ref right, .. }
+            | ASTNode::Logical { ref left, ref right, .. } => vec![left.clone(), right.clone()],
+            ASTNode::Unary { ref right, .. } => vec![right.clone()],
+            ASTNode::Grouping { ref expr } => vec![expr.clone()],
+            ASTNode::VarDecl { ref initializer, .. } => initializer.iter().cloned().collect(),
+            ASTNode::Test { ref body, .. } => body.clone(),
+            ASTNode::Assert { ref condition, ref message, .. } => std::iter::once(condition).chain(message).cloned().collect(),
+            ASTNode::Literal { .. } | ASTNode::Variable { .. } => Vec::new(),
+        }
+    }
+
+    fn error(id: Json, code: f64, message: String) -> Json {
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            ("error", Json::object([("code", Json::Number(code)), ("message", message.into())])),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames messages, runs the server over them and parses everything it sent back.
+    #[track_caller]
+    fn session(messages: Vec<Json>) -> Vec<Json> {
+        let mut input = Vec::new();
+        for message in &messages {
+            write_message(&mut input, message).unwrap();
+        }
+
+        let mut output = Vec::new();
+        run(std::io::Cursor::new(input), &mut output).unwrap();
+
+        let mut output = std::io::Cursor::new(output);
+        let mut replies = Vec::new();
+        while let Some(body) = read_message(&mut output).unwrap() {
+            replies.push(Json::parse(&body).unwrap());
+        }
+        replies
+    }
+
+    fn request(id: usize, method: &str, params: Json) -> Json {
+        Json::object([("jsonrpc", "2.0".into()), ("id", id.into()), ("method", method.into()), ("params", params)])
+    }
+
+    fn notification(method: &str, params: Json) -> Json {
+        Json::object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+    }
+
+    fn at(line: usize, character: usize) -> Json {
+        Json::object([
+            ("textDocument", Json::object([("uri", "file:///main.hasty".into())])),
+            ("position", Json::object([("line", line.into()), ("character", character.into())])),
+        ])
+    }
+
+    #[test]
+    fn test_session() {
+        let replies = session(vec![
+            request(1, "initialize", Json::object([])),
+            notification("textDocument/didOpen", Json::object([
+                ("textDocument", Json::object([
+                    ("uri", "file:///main.hasty".into()),
+                    ("text", "let x = 1;\nconst y = x + z;\n".into()),
+                ])),
+            ])),
+            request(2, "textDocument/hover", at(1, 10)),
+            request(3, "textDocument/definition", at(1, 10)),
+            request(4, "textDocument/documentSymbol", at(0, 0)),
+            request(5, "textDocument/completion", at(1, 10)),
+            request(6, "unknown", Json::Null),
+            request(7, "shutdown", Json::Null),
+            request(8, "textDocument/hover", at(1, 10)),
+            notification("textDocument/didClose", at(0, 0)),
+            notification("exit", Json::Null),
+            request(9, "shutdown", Json::Null),
+        ]);
+        assert_eq!(replies.len(), 9);
+
+        assert_eq!(replies[0].path(&["result", "capabilities", "hoverProvider"]), Some(&Json::Bool(true)));
+
+        let diagnostics = replies[1].path(&["params", "diagnostics"]).and_then(Json::as_array).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].get("message").and_then(Json::as_str), Some("Undefined variable 'z'."));
+        assert_eq!(diagnostics[0].get("code").and_then(Json::as_str), Some("E0202"));
+        assert_eq!(diagnostics[0].get("range").unwrap().to_string(), r#"{"start":{"line":1,"character":14},"end":{"line":1,"character":15}}"#);
+
+        assert_eq!(replies[2].path(&["result", "contents", "value"]).and_then(Json::as_str), Some("x: int"));
+        assert_eq!(
+            replies[3].path(&["result", "range"]).unwrap().to_string(),
+            r#"{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}"#
+        );
+
+        let symbols = replies[4].get("result").and_then(Json::as_array).unwrap();
+        let symbols: Vec<_> = symbols.iter().map(|s| (s.get("name").unwrap().to_string(), s.get("kind").unwrap().to_string())).collect();
+        assert_eq!(symbols, [("\"x\"".to_string(), "13".to_string()), ("\"y\"".to_string(), "14".to_string())]);
+
+        let completion = replies[5].get("result").and_then(Json::as_array).unwrap();
+        assert_eq!(completion[0].to_string(), r#"{"label":"x","kind":6,"detail":"int"}"#);
+        assert_eq!(completion[1].get("label").and_then(Json::as_str), Some("let"));
+
+        assert_eq!(replies[6].path(&["error", "code"]).and_then(Json::as_f64), Some(METHOD_NOT_FOUND));
+        assert_eq!(replies[7].get("result"), Some(&Json::Null));
+        assert_eq!(replies[8].path(&["error", "code"]).and_then(Json::as_f64), Some(INVALID_REQUEST));
+    }
+
+    #[test]
+    fn test_session_test_declarations() {
+        let replies = session(vec![
+            notification("textDocument/didOpen", Json::object([
+                ("textDocument", Json::object([
+                    ("uri", "file:///main.hasty".into()),
+                    ("text", "let x = 1;\ntest fn works() {\n    let y = x;\n    assert(y == 1)\n}\n".into()),
+                ])),
+            ])),
+            request(1, "textDocument/definition", at(3, 11)),
+            request(2, "textDocument/completion", at(3, 11)),
+            request(3, "textDocument/completion", at(5, 0)),
+        ]);
+
+        assert_eq!(
+            replies[1].path(&["result", "range"]).unwrap().to_string(),
+            r#"{"start":{"line":2,"character":8},"end":{"line":2,"character":9}}"#
+        );
+
+        let variables = |reply: &Json| -> Vec<String> {
+            reply.get("result").and_then(Json::as_array).unwrap().iter()
+                .filter(|item| item.get("kind").and_then(Json::as_f64) == Some(COMPLETION_VARIABLE as f64))
+                .filter_map(|item| item.get("label").and_then(Json::as_str).map(str::to_string))
+                .collect()
+        };
+        assert_eq!(variables(&replies[2]), ["x", "y"]);
+        assert_eq!(variables(&replies[3]), ["x"]);
+    }
+
+    #[test]
+    fn test_session_project_lints() {
+        // Paths in URIs are percent-encoded.
+        let directory = tempfile::Builder::new().prefix("hasty lsp é").tempdir().unwrap();
+        std::fs::write(directory.path().join("hasty.toml"), "[lints]\nunused-variable = \"deny\"\n").unwrap();
+        let uri = Url::from_file_path(directory.path().join("main.hasty")).unwrap().to_string();
+        assert!(uri.contains("%20"));
+
+        let replies = session(vec![
+            notification("textDocument/didOpen", Json::object([
+                ("textDocument", Json::object([("uri", uri.as_str().into()), ("text", "let unused = 1;\n".into())])),
+            ])),
+        ]);
+
+        let diagnostics = replies[0].path(&["params", "diagnostics"]).and_then(Json::as_array).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].get("severity").and_then(Json::as_usize), Some(SEVERITY_ERROR));
+    }
+
+    #[test]
+    fn test_session_unexpected_characters() {
+        let replies = session(vec![
+            notification("textDocument/didOpen", Json::object([
+                ("textDocument", Json::object([("uri", "file:///main.hasty".into()), ("text", "let aé = 1;\nlet b = 'c';\n".into())])),
+            ])),
+            notification("textDocument/didChange", Json::object([
+                ("textDocument", Json::object([("uri", "file:///main.hasty".into())])),
+                ("contentChanges", vec![Json::object([("text", "let aé = 1;\naé\n".into())])].into()),
+            ])),
+            request(1, "textDocument/hover", at(1, 1)),
+        ]);
+        assert_eq!(replies.len(), 3);
+
+        let diagnostics = replies[0].path(&["params", "diagnostics"]).and_then(Json::as_array).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].get("code").and_then(Json::as_str), Some("E0001"));
+        assert_eq!(diagnostics[0].get("range").unwrap().to_string(), r#"{"start":{"line":1,"character":8},"end":{"line":1,"character":9}}"#);
+
+        let diagnostics = replies[1].path(&["params", "diagnostics"]).and_then(Json::as_array).unwrap();
+        let codes: Vec<_> = diagnostics.iter().filter_map(|d| d.get("code").and_then(Json::as_str)).collect();
+        assert_eq!(codes, ["W0002"]);
+        assert_eq!(replies[2].path(&["result", "contents", "value"]).and_then(Json::as_str), Some("aé: int"));
+    }
+}
@@ -0,0 +1,494 @@
+use std::{io::{IsTerminal, Read, Write}, path::{Path, PathBuf}, process::ExitCode};
+
+use rshasty::{
+    compiler::{CompilerOptions, EcmaVersion, ModuleFormat},
+    formatter::Formatter,
+    parser::{ast::ASTNodeVecExt, Parser},
+    passes::lint::{Lint, LintConfig, LintLevel, LINTS},
+    repl::{Feed, Repl},
+    scanner::Scanner,
+    test_runner::TestRunner,
+    util::{diagnostic::Diagnostic, HastyError},
+    Diagnostics,
+};
+
+const USAGE: &str = "\
+Usage: hasty <command> [options] [file]
+       hasty test [options] [path...]
+
+Reads source from `file`, or from stdin if it is missing or `-`.
+
+Commands:
+    build     Compile to javascript
+    check     Report errors and warnings without compiling
+    tokens    Print tokens produced by the scanner
+    ast       Print AST produced by the parser
+    fmt       Format source code, rewriting `file` in place
+    repl      Start interactive session
+    lsp       Start language server on stdin and stdout
+    lints     List available lints and their default levels
+    test      Run `test fn` declarations in files, or in .hasty files in directories
+              (current directory if none are given)
+
+Build options:
+    -o, --output <file>     Write javascript to a file instead of stdout
+    --minify                Emit minified code
+    --format <format>       Module format: esm (default), commonjs, iife
+    --target <version>      ECMAScript version: es5, es2015 (default), es2020
+
+Common options:
+    --message-format <format>
+                            Diagnostics format: human (default), or json with one object per line
+
+Lint options (check, build):
+    --allow <lint>, --warn <lint>, --deny <lint>
+                            Set level of a lint, overriding the project file
+
+Test options:
+    --filter <name>         Only run tests whose name contains `name`
+
+Format options:
+    --check                 Only check whether source is formatted, exit with 1 if it is not
+
+Lint levels are read from the [lints] table of hasty.toml, in the directory of `file`
+or the closest parent that has one (current directory for stdin).
+
+Diagnostics are written to stderr. Colors are disabled if NO_COLOR is set or stderr
+is not a terminal, unless CLICOLOR_FORCE is set.
+
+Exit codes:
+    0    Success
+    1    Program has errors, is not formatted with `fmt --check`, or a test failed
+    2    Invalid usage
+    3    Input or output could not be read or written
+";
+
+const EXIT_ERRORS: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_IO: u8 = 3;
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Build,
+    Check,
+    Tokens,
+    Ast,
+    Fmt,
+    Repl,
+    Lsp,
+    Lints,
+    Test,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MessageFormat {
+    Human,
+    Json,
+}
+
+#[derive(Debug)]
+struct Args {
+    command: Command,
+    input: Option<String>,
+    output: Option<String>,
+    options: CompilerOptions,
+    /// Only check formatting.
+    check: bool,
+    message_format: MessageFormat,
+    /// Lint levels given on the command line.
+    lints: Vec<(String, LintLevel)>,
+    /// Files and directories with tests.
+    paths: Vec<String>,
+    /// Only run tests with names containing it.
+    filter: Option<String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let command = match args.next().as_deref() {
+            Some("build") => Command::Build,
+            Some("check") => Command::Check,
+            Some("tokens") => Command::Tokens,
+            Some("ast") => Command::Ast,
+            Some("fmt") => Command::Fmt,
+            Some("repl") => Command::Repl,
+            Some("lsp") => Command::Lsp,
+            Some("lints") => Command::Lints,
+            Some("test") => Command::Test,
+            Some(command) => return Err(format!("Unknown command '{}'.", command)),
+            None => return Err("Missing command.".to_string()),
+        };
+
+        let mut result = Args { command, input: None, output: None, options: CompilerOptions::default(), check: false, message_format: MessageFormat::Human, lints: Vec::new(), paths: Vec::new(), filter: None };
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("Missing value for '{}'.", name));
+            match arg.as_str() {
+                "-o" | "--output" => result.output = Some(value(&arg)?),
+                "--minify" => result.options.minify = true,
+                "--check" => result.check = true,
+                "--filter" => result.filter = Some(value(&arg)?),
+                "--allow" | "--warn" | "--deny" => {
+                    let lint = value(&arg)?;
+                    if Lint::find(&lint).is_none() {
+                        return Err(format!("Unknown lint '{}'.", lint));
+                    }
+                    result.lints.push((lint, LintLevel::parse(&arg[2..]).unwrap()));
+                },
+                "--message-format" => result.message_format = match value(&arg)?.as_str() {
+                    "human" => MessageFormat::Human,
+                    "json" => MessageFormat::Json,
+                    format => return Err(format!("Unknown message format '{}'.", format)),
+                },
+                "--format" => result.options.format = match value(&arg)?.as_str() {
+                    "esm" => ModuleFormat::Esm,
+                    "commonjs" => ModuleFormat::CommonJs,
+                    "iife" => ModuleFormat::Iife,
+                    format => return Err(format!("Unknown module format '{}'.", format)),
+                },
+                "--target" => result.options.target = match value(&arg)?.as_str() {
+                    "es5" => EcmaVersion::Es5,
+                    "es2015" => EcmaVersion::Es2015,
+                    "es2020" => EcmaVersion::Es2020,
+                    target => return Err(format!("Unknown target '{}'.", target)),
+                },
+                "-" => result.input = None,
+                _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'.", arg)),
+                _ if result.command == Command::Test => result.paths.push(arg),
+                _ if result.input.is_some() => return Err("Only one input file can be given.".to_string()),
+                _ => result.input = Some(arg),
+            }
+        }
+
+        if result.output.is_some() && result.command != Command::Build {
+            return Err("Output file can only be given to 'build'.".to_string());
+        }
+        if result.check && result.command != Command::Fmt {
+            return Err("'--check' can only be given to 'fmt'.".to_string());
+        }
+        if result.filter.is_some() && result.command != Command::Test {
+            return Err("'--filter' can only be given to 'test'.".to_string());
+        }
+        if !result.lints.is_empty() && !matches!(result.command, Command::Check | Command::Build) {
+            return Err("Lint levels can only be given to 'check' and 'build'.".to_string());
+        }
+        Ok(result)
+    }
+}
+
+/// Reason why command failed, mapped to an exit code.
+enum Failure {
+    /// Errors were already reported.
+    Errors,
+    Io(String, std::io::Error),
+}
+
+fn main() -> ExitCode {
+    configure_colors();
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        },
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Errors) => ExitCode::from(EXIT_ERRORS),
+        Err(Failure::Io(path, error)) => {
+            eprintln!("Could not access {}: {}", path, error);
+            ExitCode::from(EXIT_IO)
+        },
+    }
+}
+
+fn run(args: Args) -> Result<(), Failure> {
+    match args.command {
+        Command::Repl => return repl(),
+        Command::Lsp => {
+            return rshasty::lsp::run(std::io::stdin().lock(), std::io::stdout())
+                .map_err(|error| Failure::Io("stdio".to_string(), error));
+        },
+        Command::Lints => {
+            let lints = LINTS.iter()
+                .map(|lint| format!("{:<24}{:<8}{}\n", lint.id, lint.default_level.to_string(), lint.description))
+                .collect::<String>();
+            return write_output(None, &lints);
+        },
+        Command::Test => return test(&args),
+        _ => {},
+    }
+
+    let source = read_input(args.input.as_deref())?;
+    let reporter = Reporter { source: &source, file: args.input.as_deref(), format: args.message_format };
+
+    match args.command {
+        Command::Tokens => {
+            let tokens = Scanner::new(&source).scan().map_err(|error| reporter.error(error))?;
+            write_output(None, &tokens.iter().map(|token| format!("{}\n", token)).collect::<String>())
+        },
+        Command::Ast => {
+            let tokens = Scanner::new(&source).scan().map_err(|error| reporter.error(error))?;
+            let ast = Parser::new(tokens).parse().map_err(|error| reporter.error(error))?;
+            write_output(None, &ast.display(0))
+        },
+        Command::Fmt => {
+            let formatted = Formatter::new().format(&source).map_err(|diagnostics| reporter.errors(diagnostics))?;
+            let path = args.input.as_deref();
+            if args.check {
+                if formatted == source { return Ok(()); }
+                eprintln!("{} is not formatted", path.unwrap_or("stdin"));
+                return Err(Failure::Errors);
+            }
+            // Files are only rewritten if they change, to keep their modification time.
+            if path.is_none() || formatted != source {
+                write_output(path, &formatted)?;
+            }
+            Ok(())
+        },
+        Command::Check => {
+            let lints = lint_config(args.input.as_deref(), &args)?;
+            let analysis = rshasty::analyze_with(&source, &lints).map_err(|diagnostics| reporter.errors(diagnostics))?;
+            reporter.warnings(&analysis.warnings);
+            Ok(())
+        },
+        Command::Build => {
+            let lints = lint_config(args.input.as_deref(), &args)?;
+            let output = rshasty::compile_with(&source, args.options, &lints).map_err(|diagnostics| reporter.errors(diagnostics))?;
+            reporter.warnings(&output.warnings);
+            write_output(args.output.as_deref(), &output.code)
+        },
+        Command::Repl | Command::Lsp | Command::Lints | Command::Test => unreachable!(),
+    }
+}
+
+/// Runs tests of every file, printing the result of each test and a summary.
+fn test(args: &Args) -> Result<(), Failure> {
+    let paths = if args.paths.is_empty() { vec![".".to_string()] } else { args.paths.clone() };
+    let mut files = Vec::new();
+    for path in &paths {
+        collect_sources(Path::new(path), &mut files)?;
+    }
+    let runner = match &args.filter {
+        Some(filter) => TestRunner::with_filter(filter),
+        None => TestRunner::new(),
+    };
+
+    let (mut passed, mut failed, mut filtered, mut broken) = (0, 0, 0, 0);
+    for file in &files {
+        let path = file.strip_prefix(".").unwrap_or(file).display().to_string();
+        let source = read_input(Some(&path))?;
+        let reporter = Reporter { source: &source, file: Some(&path), format: args.message_format };
+
+        let lints = lint_config(Some(&path), args)?;
+        let analysis = match rshasty::analyze_with(&source, &lints) {
+            Ok(analysis) => analysis,
+            Err(diagnostics) => {
+                eprintln!("error: {} could not be compiled", path);
+                reporter.errors(diagnostics);
+                broken += 1;
+                continue;
+            },
+        };
+
+        let report = runner.run(&analysis.ast);
+        filtered += report.filtered;
+        for result in report.results {
+            match result.outcome {
+                Ok(()) => {
+                    println!("test {}::{} ... ok", path, result.name);
+                    passed += 1;
+                },
+                Err(error) => {
+                    println!("test {}::{} ... FAILED", path, result.name);
+                    reporter.error(error);
+                    failed += 1;
+                },
+            }
+        }
+    }
+
+    let status = if failed + broken == 0 { "ok" } else { "FAILED" };
+    println!("\ntest result: {}. {} passed; {} failed; {} filtered out", status, passed, failed, filtered);
+    if broken > 0 {
+        eprintln!("{} of {} files could not be compiled", broken, files.len());
+    }
+    if failed + broken == 0 { Ok(()) } else { Err(Failure::Errors) }
+}
+
+/// Adds `path` if it is a file, or every `.hasty` file in it if it is a directory, in sorted order.
+fn collect_sources(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), Failure> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let io = |error| Failure::Io(path.display().to_string(), error);
+    let mut entries = std::fs::read_dir(path).map_err(io)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>().map_err(io)?;
+    entries.sort();
+
+    for entry in entries {
+        let hidden = entry.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if entry.is_dir() && !hidden {
+            collect_sources(&entry, files)?;
+        } else if entry.extension().is_some_and(|extension| extension == "hasty") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Lint levels from the project file of `input`, overridden by the command line.
+fn lint_config(input: Option<&str>, args: &Args) -> Result<LintConfig, Failure> {
+    let directory = match input {
+        Some(input) => std::fs::canonicalize(input).map_err(|error| Failure::Io(input.to_string(), error))?
+            .parent().map(Path::to_path_buf).unwrap_or_default(),
+        None => std::env::current_dir().map_err(|error| Failure::Io("current directory".to_string(), error))?,
+    };
+
+    let mut config = match LintConfig::find_project_file(&directory) {
+        Some(path) => {
+            let text = std::fs::read_to_string(&path).map_err(|error| Failure::Io(path.display().to_string(), error))?;
+            LintConfig::parse(&text).map_err(|error| {
+                eprintln!("Invalid {}: {}", path.display(), error);
+                Failure::Errors
+            })?
+        },
+        None => LintConfig::new(),
+    };
+    for (lint, level) in &args.lints {
+        config.set(lint, *level).expect("lints are checked when parsing arguments");
+    }
+    Ok(config)
+}
+
+fn repl() -> Result<(), Failure> {
+    let mut repl = Repl::new();
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+
+    loop {
+        print!("{}", repl.prompt());
+        stdout.flush().map_err(|error| Failure::Io("stdout".to_string(), error))?;
+
+        let mut line = String::new();
+        let read = stdin.read_line(&mut line).map_err(|error| Failure::Io("stdin".to_string(), error))?;
+        if read == 0 {
+            println!();
+            return Ok(());
+        }
+
+        match repl.feed(line.trim_end_matches(['\n', '\r'])) {
+            Feed::Incomplete => {},
+            Feed::Output(output) if output.is_empty() => {},
+            Feed::Output(output) => println!("{}", output),
+            Feed::Error(error) => eprintln!("{}", error),
+            Feed::Exit => return Ok(()),
+        }
+    }
+}
+
+/// Disables colors if `NO_COLOR` is set or stderr is not a terminal, unless `CLICOLOR_FORCE` is set.
+fn configure_colors() {
+    let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty() && value != "0");
+    if set("CLICOLOR_FORCE") {
+        colored::control::set_override(true);
+    } else if set("NO_COLOR") || !std::io::stderr().is_terminal() {
+        colored::control::set_override(false);
+    }
+}
+
+/// Writes diagnostics of a single source to stderr.
+struct Reporter<'a> {
+    source: &'a str,
+    /// Path of the source, if it was read from a file.
+    file: Option<&'a str>,
+    format: MessageFormat,
+}
+
+impl Reporter<'_> {
+    fn emit(&self, diagnostic: Diagnostic) {
+        match self.format {
+            MessageFormat::Human => eprintln!("{}", diagnostic.render(self.source)),
+            MessageFormat::Json => eprintln!("{}", diagnostic.to_json(self.source, self.file)),
+        }
+    }
+
+    fn errors(&self, diagnostics: Diagnostics) -> Failure {
+        for diagnostic in diagnostics.diagnostics() {
+            self.emit(diagnostic);
+        }
+        Failure::Errors
+    }
+
+    fn error(&self, error: impl HastyError) -> Failure {
+        self.emit(error.diagnostic());
+        Failure::Errors
+    }
+
+    fn warnings(&self, warnings: &[Box<dyn HastyError>]) {
+        for warning in warnings {
+            self.emit(warning.diagnostic());
+        }
+    }
+}
+
+fn read_input(path: Option<&str>) -> Result<String, Failure> {
+    match path {
+        Some(path) => std::fs::read_to_string(path).map_err(|error| Failure::Io(path.to_string(), error)),
+        None => {
+            let mut source = String::new();
+            std::io::stdin().read_to_string(&mut source)
+                .map_err(|error| Failure::Io("stdin".to_string(), error))?;
+            Ok(source)
+        },
+    }
+}
+
+fn write_output(path: Option<&str>, output: &str) -> Result<(), Failure> {
+    match path {
+        Some(path) => std::fs::write(path, output).map_err(|error| Failure::Io(path.to_string(), error)),
+        None => std::io::stdout().write_all(output.as_bytes())
+            .map_err(|error| Failure::Io("stdout".to_string(), error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse(&["build", "main.hasty", "-o", "main.js", "--minify", "--format", "commonjs"]).unwrap();
+        assert_eq!(args.command, Command::Build);
+        assert_eq!(args.input.as_deref(), Some("main.hasty"));
+        assert_eq!(args.output.as_deref(), Some("main.js"));
+        assert!(args.options.minify);
+        assert_eq!(args.options.format, ModuleFormat::CommonJs);
+
+        assert!(parse(&["tokens", "-"]).unwrap().input.is_none());
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["run"]).is_err());
+        assert!(parse(&["build", "--target"]).is_err());
+        assert!(parse(&["check", "-o", "out.js"]).is_err());
+        assert!(parse(&["ast", "a.hasty", "b.hasty"]).is_err());
+        assert!(parse(&["fmt", "--check"]).unwrap().check);
+        assert!(parse(&["build", "--check"]).is_err());
+        assert_eq!(parse(&["check", "--message-format", "json"]).unwrap().message_format, MessageFormat::Json);
+        assert!(parse(&["check", "--message-format", "xml"]).is_err());
+        assert_eq!(parse(&["build", "--deny", "self-comparison"]).unwrap().lints, vec![("self-comparison".to_string(), LintLevel::Deny)]);
+        assert!(parse(&["check", "--allow", "everything"]).is_err());
+        assert!(parse(&["fmt", "--warn", "unused-variable"]).is_err());
+        let args = parse(&["test", "math.hasty", "tests", "--filter", "add"]).unwrap();
+        assert_eq!((args.paths, args.filter.as_deref()), (vec!["math.hasty".to_string(), "tests".to_string()], Some("add")));
+        assert!(parse(&["check", "--filter", "add"]).is_err());
+    }
+}
@@ -1,10 +1,11 @@
 use crate::parser::ast::ASTNode;
 use crate::scanner::{Token, TokenType};
-use crate::util::{HastyError, unified_error};
+use crate::util::{diagnostic::{Diagnostic, Span}, HastyError, unified_error};
 
 use self::ast::BoxedASTNode;
 
 pub mod ast;
+pub mod visitor;
 
 /// Struct for parsing tokens into AST.
 #[derive(Debug)]
@@ -17,39 +18,82 @@ pub struct Parser {
 pub enum ParserErrorTy {
     ExpectedToken {
         token: TokenType
-    }
+    },
+    ExpectedExpression,
 }
 
 #[derive(Debug)]
 pub struct ParserError {
     ty: ParserErrorTy,
-    token: Token,
+    // Tokens are boxed to keep results of parsing functions small.
+    token: Box<Token>,
+    /// Token before the unexpected one, used to suggest missing tokens.
+    previous: Option<Box<Token>>,
 }
 
 impl ParserError {
     pub fn new(ty: ParserErrorTy, token: Token) -> Self {
         Self {
             ty,
-            token,
+            token: Box::new(token),
+            previous: None,
         }
     }
+
+    /// Source code of tokens that can be suggested when missing.
+    fn symbol(ty: &TokenType) -> Option<&'static str> {
+        Some(match ty {
+            TokenType::SEMICOLON => ";",
+            TokenType::RIGHT_PAREN => ")",
+            TokenType::EQUAL => "=",
+            TokenType::LET => "let",
+            TokenType::LEFT_BRACE => "{",
+            TokenType::RIGHT_BRACE => "}",
+            _ => return None,
+        })
+    }
 }
 
 impl HastyError for ParserError {
-    fn as_hasty_error_string(&self) -> String {
-        // TODO: Put line str here
-        unified_error(
-            "PARSER", &self.get_error_description(),
-            self.token.line, self.token.start,
-            &self.token.lexeme, ""
-        )
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("PARSER", &self.get_error_description(), source, &self.token)
     }
 
     fn get_error_description(&self) -> String {
         match &self.ty {
-            ParserErrorTy::ExpectedToken { token } => format!("Expected {:?}.", token)
+            ParserErrorTy::ExpectedToken { token } => format!("Expected {:?}.", token),
+            ParserErrorTy::ExpectedExpression => "Expected expression.".to_string(),
         }.to_string()
     }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+
+    fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(self.get_error_description());
+        match &self.ty {
+            ParserErrorTy::ExpectedToken { token } => {
+                let symbol = Self::symbol(token);
+                let label = match symbol {
+                    Some(symbol) => format!("expected '{}'", symbol),
+                    None => format!("expected {:?}", token),
+                };
+                let diagnostic = diagnostic.with_code("E0101").with_primary(&*self.token, label);
+
+                // Missing punctuation belongs right after the previous token.
+                match (symbol, &self.previous, token) {
+                    (Some(symbol), Some(previous), TokenType::SEMICOLON | TokenType::RIGHT_PAREN) => {
+                        let end = previous.start + previous.lexeme.len();
+                        diagnostic.with_suggestion(format!("add '{}'", symbol), Span::new(end, end), symbol)
+                    },
+                    _ => diagnostic,
+                }
+            },
+            ParserErrorTy::ExpectedExpression => diagnostic.with_code("E0102")
+                .with_primary(&*self.token, "expected expression"),
+        }
+    }
 }
 
 impl Parser {
@@ -75,6 +119,13 @@ impl Parser {
         self.tokens.get(self.current - 1).unwrap()
     }
 
+    /// Whether current token is an identifier with given name, followed by token of type `next`.
+    /// Used for words that are only keywords in some places, like `test` and `assert`.
+    fn check_contextual(&self, name: &str, next: TokenType) -> bool {
+        self.check(TokenType::IDENTIFIER) && self.peek().lexeme == name
+            && self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == next)
+    }
+
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() { self.current += 1; }
         self.previous()
@@ -89,17 +140,17 @@ impl Parser {
             self.advance();
             return true;
         }
-        return false;
+        false
     }
 
-    fn match_any(&mut self, types: &Vec<TokenType>) -> bool {
+    fn match_any(&mut self, types: &[TokenType]) -> bool {
         for ty in types.iter() {
             if self.check(ty.clone()) {
                 self.advance();
                 return true;
             }
         }
-        return false;
+        false
     }
 
     fn expect(&mut self, ty: TokenType, error: ParserError) -> Result<(), ParserError> {
@@ -111,7 +162,9 @@ impl Parser {
     }
 
     fn parser_error(&self, et: ParserErrorTy) -> Result<(), ParserError> {
-        Err(ParserError::new(et, self.peek().clone()))
+        let mut error = ParserError::new(et, self.peek().clone());
+        error.previous = self.current.checked_sub(1).map(|i| Box::new(self.tokens[i].clone()));
+        Err(error)
     }
 
     // Basics.
@@ -126,13 +179,74 @@ impl Parser {
 
     // Declarations.
     fn declaration(&mut self) -> Result<ASTNode, ParserError> {
+        let public = self.try_match(TokenType::PUB);
+
         if self.try_match(TokenType::LET) {
-            return self.var_declaration();
+            return self.var_declaration(false, public);
+        }
+
+        if self.try_match(TokenType::CONST) {
+            return self.var_declaration(true, public);
+        }
+
+        if public {
+            return Err(self.parser_error(ParserErrorTy::ExpectedToken { token: TokenType::LET }).unwrap_err());
+        }
+
+        if self.check_contextual("test", TokenType::FN) {
+            return self.test_declaration();
         }
 
         self.statement()
     }
 
+    /// test_declaration -> "test" "fn" IDENTIFIER "(" ")" "{" test_statement* "}";
+    fn test_declaration(&mut self) -> Result<ASTNode, ParserError> {
+        self.advance();
+        self.consume(TokenType::FN)?;
+        let name = self.consume(TokenType::IDENTIFIER)?;
+        self.consume(TokenType::LEFT_PAREN)?;
+        self.consume(TokenType::RIGHT_PAREN)?;
+        self.consume(TokenType::LEFT_BRACE)?;
+
+        let mut body = Vec::new();
+        while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            body.push(self.test_statement()?.boxed());
+        }
+        let end = self.consume(TokenType::RIGHT_BRACE)?;
+
+        Ok(ASTNode::Test { name, body, end })
+    }
+
+    /// test_statement -> assert | ( "let" | "const" ) var_declaration | statement;
+    fn test_statement(&mut self) -> Result<ASTNode, ParserError> {
+        if self.check_contextual("assert", TokenType::LEFT_PAREN) {
+            return self.assert();
+        }
+        if self.try_match(TokenType::LET) {
+            return self.var_declaration(false, false);
+        }
+        if self.try_match(TokenType::CONST) {
+            return self.var_declaration(true, false);
+        }
+        self.statement()
+    }
+
+    /// assert -> "assert" "(" expression ( "," expression )? ")";
+    fn assert(&mut self) -> Result<ASTNode, ParserError> {
+        let keyword = self.advance().clone();
+        self.consume(TokenType::LEFT_PAREN)?;
+        let condition = self.expression()?.boxed();
+        let message = if self.try_match(TokenType::COMMA) {
+            Some(self.expression()?.boxed())
+        } else {
+            None
+        };
+        self.consume(TokenType::RIGHT_PAREN)?;
+
+        Ok(ASTNode::Assert { keyword, condition, message })
+    }
+
     // Statements.
     fn statement(&mut self) -> Result<ASTNode, ParserError> {
         self.expression()
@@ -147,7 +261,7 @@ impl Parser {
     fn logic_or(&mut self) -> Result<ASTNode, ParserError> {
         let mut expr: ASTNode = self.logic_and()?;
 
-        while self.match_any(&vec![TokenType::OR]) {
+        while self.match_any(&[TokenType::OR]) {
             let operator = self.previous().clone();
             let right = self.logic_and()?;
             expr = ASTNode::Logical {
@@ -164,7 +278,7 @@ impl Parser {
     fn logic_and(&mut self) -> Result<ASTNode, ParserError> {
         let mut expr: ASTNode = self.equality()?;
 
-        while self.match_any(&vec![TokenType::AND]) {
+        while self.match_any(&[TokenType::AND]) {
             let operator = self.previous().clone();
             let right = self.equality()?;
             expr = ASTNode::Logical {
@@ -181,7 +295,7 @@ impl Parser {
     fn equality(&mut self) -> Result<ASTNode, ParserError> {
         let mut expr: ASTNode = self.comparison()?;
 
-        while self.match_any(&vec![TokenType::BANG_EQUAL, TokenType::EQUAL_EQUAL]) {
+        while self.match_any(&[TokenType::BANG_EQUAL, TokenType::EQUAL_EQUAL]) {
             let operator = self.previous().clone();
             let right = self.comparison()?;
             expr = ASTNode::Binary {
@@ -198,7 +312,7 @@ impl Parser {
     fn comparison(&mut self) -> Result<ASTNode, ParserError> {
         let mut expr = self.term()?;
 
-        while self.match_any(&vec![
+        while self.match_any(&[
             TokenType::GREATER, TokenType::GREATER_EQUAL, TokenType::LESS, TokenType::LESS_EQUAL
         ]) {
             let operator = self.previous().clone();
@@ -217,7 +331,7 @@ impl Parser {
     fn term(&mut self) -> Result<ASTNode, ParserError> {
         let mut expr = self.factor()?;
 
-        while self.match_any(&vec![TokenType::MINUS, TokenType::PLUS]) {
+        while self.match_any(&[TokenType::MINUS, TokenType::PLUS]) {
             let operator = self.previous().clone();
             let right = self.factor()?;
             expr = ASTNode::Binary {
@@ -234,7 +348,7 @@ impl Parser {
     fn factor(&mut self) -> Result<ASTNode, ParserError> {
         let mut expr = self.unary()?;
 
-        while self.match_any(&vec![TokenType::SLASH, TokenType::STAR]) {
+        while self.match_any(&[TokenType::SLASH, TokenType::STAR]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
             expr = ASTNode::Binary {
@@ -249,7 +363,7 @@ impl Parser {
 
     /// unary -> ( "!" | "-" ) unary | primary;
     fn unary(&mut self) -> Result<ASTNode, ParserError> {
-        if self.match_any(&vec![TokenType::BANG, TokenType::MINUS]) {
+        if self.match_any(&[TokenType::BANG, TokenType::MINUS]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
             return Ok(ASTNode::Unary {
@@ -258,18 +372,18 @@ impl Parser {
             });
         }
 
-        return self.primary();
+        self.primary()
     }
 
     /// primary -> NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")";
     fn primary(&mut self) -> Result<ASTNode, ParserError> {
-        if self.match_any(&vec![TokenType::TRUE, TokenType::FALSE, TokenType::NIL]) {
+        if self.match_any(&[TokenType::TRUE, TokenType::FALSE, TokenType::NIL]) {
             return Ok(ASTNode::Literal {
                 value: self.previous().clone(),
             });
         }
 
-        if self.match_any(&vec![TokenType::INTEGER, TokenType::FLOATING, TokenType::STRING]) {
+        if self.match_any(&[TokenType::INTEGER, TokenType::FLOATING, TokenType::STRING]) {
            return Ok(ASTNode::Literal {
                value: self.previous().clone(),
            });
@@ -292,11 +406,12 @@ impl Parser {
             });
         }
 
-        todo!()
+        Err(ParserError::new(ParserErrorTy::ExpectedExpression, self.peek().clone()))
     }
 
-    /// var_declaration -> "let" IDENTIFIER: [type] ( "=" expression )? ";";
-    fn var_declaration(&mut self) -> Result<ASTNode, ParserError> {
+    /// var_declaration -> "pub"? "let" IDENTIFIER: [type] ( "=" expression )? ";"
+    ///                  | "pub"? "const" IDENTIFIER: [type] "=" expression ";";
+    fn var_declaration(&mut self, constant: bool, public: bool) -> Result<ASTNode, ParserError> {
         let name = self.consume(TokenType::IDENTIFIER)?;
         let ty = if self.try_match(TokenType::COLON) {
             Some(self.consume(TokenType::IDENTIFIER)?)
@@ -304,7 +419,9 @@ impl Parser {
             None
         };
 
-        let initializer = if self.try_match(TokenType::EQUAL) {
+        // Constants always have to be initialized.
+        if constant { self.consume(TokenType::EQUAL)?; }
+        let initializer = if constant || self.try_match(TokenType::EQUAL) {
             Some(self.expression()?)
         } else {
             None
@@ -316,6 +433,8 @@ impl Parser {
             name,
             ty,
             initializer: initializer.map(|x| x.boxed()),
+            constant,
+            public,
         })
     }
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use std::{fmt::Display, cell::RefCell, rc::Rc};
 
-use crate::{scanner::Token, util::metacontainer::MetaContainer};
+use crate::{scanner::Token, util::{diagnostic::Span, metacontainer::MetaContainer}};
 
 /// Boxed AST Node with metadata
 #[derive(Debug, Clone)]
@@ -10,11 +10,11 @@ pub struct BoxedASTNode {
 }
 
 impl BoxedASTNode {
-    pub fn borrow(&self) -> std::cell::Ref<Box<ASTNode>> {
+    pub fn borrow(&self) -> std::cell::Ref<'_, Box<ASTNode>> {
         self.node.borrow()
     }
 
-    pub fn borrow_mut(&self) -> std::cell::RefMut<Box<ASTNode>> {
+    pub fn borrow_mut(&self) -> std::cell::RefMut<'_, Box<ASTNode>> {
         self.node.borrow_mut()
     }
 }
@@ -68,17 +68,97 @@ pub enum ASTNode {
         name: Token,
         ty: Option<Token>,
         initializer: Option<BoxedASTNode>,
+        /// Whether this is a `const` declaration.
+        constant: bool,
+        /// Whether this declaration is exported from the module (`pub`).
+        public: bool,
     },
     /// Variable
     Variable {
         name: Token,
     },
+    /// Test declaration, `test fn name() { ... }`. Only run by the test runner.
+    Test {
+        name: Token,
+        body: Vec<BoxedASTNode>,
+        /// Closing brace of the body.
+        end: Token,
+    },
+    /// Assertion inside of a test, `assert(condition)` or `assert(condition, message)`.
+    Assert {
+        keyword: Token,
+        condition: BoxedASTNode,
+        message: Option<BoxedASTNode>,
+    },
 }
 
 impl ASTNode {
     pub fn boxed(self) -> BoxedASTNode {
         BoxedASTNode::from(self)
     }
+
+    /// Name of this node's variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ASTNode::Binary { .. } => "Binary",
+            ASTNode::Logical { .. } => "Logical",
+            ASTNode::Unary { .. } => "Unary",
+            ASTNode::Literal { .. } => "Literal",
+            ASTNode::Grouping { .. } => "Grouping",
+            ASTNode::VarDecl { .. } => "VarDecl",
+            ASTNode::Variable { .. } => "Variable",
+            ASTNode::Test { .. } => "Test",
+            ASTNode::Assert { .. } => "Assert",
+        }
+    }
+
+    /// Whether this is a test declaration, which is left out of compiled programs.
+    pub fn is_test(&self) -> bool {
+        matches!(self, ASTNode::Test { .. })
+    }
+
+    /// Token that best describes position of this node.
+    pub fn position(&self) -> Token {
+        match self {
+            ASTNode::Binary { operator, .. }
+            | ASTNode::Logical { operator, .. }
+            | ASTNode::Unary { operator, .. } => operator.clone(),
+            ASTNode::Literal { value } => value.clone(),
+            ASTNode::VarDecl { name, .. } | ASTNode::Variable { name } | ASTNode::Test { name, .. } => name.clone(),
+            ASTNode::Grouping { expr } => expr.borrow().position(),
+            ASTNode::Assert { keyword, .. } => keyword.clone(),
+        }
+    }
+
+    /// Source range from the first to the last token of this node.
+    /// Parentheses of groupings and `let` or `test fn` keywords are not included.
+    pub fn span(&self) -> Span {
+        let mut span = Span::from(&self.position());
+        let mut include = |other: Span| span = Span::new(span.start.min(other.start), span.end.max(other.end));
+
+        match self {
+            ASTNode::Binary { left, right, .. } | ASTNode::Logical { left, right, .. } => {
+                include(left.borrow().span());
+                include(right.borrow().span());
+            },
+            ASTNode::Unary { right, .. } => include(right.borrow().span()),
+            ASTNode::Grouping { expr } => include(expr.borrow().span()),
+            ASTNode::VarDecl { ty, initializer, .. } => {
+                if let Some(ty) = ty { include(Span::from(ty)); }
+                if let Some(initializer) = initializer { include(initializer.borrow().span()); }
+            },
+            ASTNode::Test { body, end, .. } => {
+                for node in body { include(node.borrow().span()); }
+                include(Span::from(end));
+            },
+            ASTNode::Assert { condition, message, .. } => {
+                include(condition.borrow().span());
+                if let Some(message) = message { include(message.borrow().span()); }
+            },
+            ASTNode::Literal { .. } | ASTNode::Variable { .. } => {},
+        }
+        span
+    }
 }
 
 impl Display for ASTNode {
@@ -101,8 +181,10 @@ impl Display for ASTNode {
                 write!(f, "({})", expr)
             }
 
-            ASTNode::VarDecl { name, ty, initializer } => {
-                write!(f, "(letvardecl {}{}{})",
+            ASTNode::VarDecl { name, ty, initializer, constant, public } => {
+                write!(f, "({}{}vardecl {}{}{})",
+                    if *public { "pub " } else { "" },
+                    if *constant { "const" } else { "let" },
                     name.lexeme,
                     if let Some(ty) = ty { format!(": {}", ty.lexeme) } else { String::new() },
                     if let Some(initializer) = initializer { format!(" = {}", initializer) } else { String::new() },
@@ -111,6 +193,19 @@ impl Display for ASTNode {
             ASTNode::Variable { name } => {
                 write!(f, "(var {})", name.lexeme)
             }
+            ASTNode::Test { name, body, .. } => {
+                write!(f, "(test {}", name.lexeme)?;
+                for node in body {
+                    write!(f, " {}", node)?;
+                }
+                write!(f, ")")
+            }
+            ASTNode::Assert { condition, message, .. } => {
+                match message {
+                    Some(message) => write!(f, "(assert {} {})", condition, message),
+                    None => write!(f, "(assert {})", condition),
+                }
+            }
         }
     }
 }
@@ -0,0 +1,303 @@
+use crate::{scanner::Token, util::metacontainer::MetaContainer};
+
+use super::ast::{ASTNode, BoxedASTNode};
+
+/// Read-only AST visitor.
+/// Every method walks into children by default, so implementors
+/// only have to override nodes they care about.
+pub trait Visitor: Sized {
+    fn visit_node(&mut self, node: &BoxedASTNode) {
+        walk_node(self, node)
+    }
+
+    fn visit_binary(&mut self, _node: &BoxedASTNode, left: &BoxedASTNode, _operator: &Token, right: &BoxedASTNode) {
+        walk_binary(self, left, right)
+    }
+
+    fn visit_logical(&mut self, _node: &BoxedASTNode, left: &BoxedASTNode, _operator: &Token, right: &BoxedASTNode) {
+        walk_logical(self, left, right)
+    }
+
+    fn visit_unary(&mut self, _node: &BoxedASTNode, _operator: &Token, right: &BoxedASTNode) {
+        walk_unary(self, right)
+    }
+
+    fn visit_literal(&mut self, _node: &BoxedASTNode, _value: &Token) {}
+
+    fn visit_grouping(&mut self, _node: &BoxedASTNode, expr: &BoxedASTNode) {
+        walk_grouping(self, expr)
+    }
+
+    fn visit_var_decl(&mut self, _node: &BoxedASTNode, _name: &Token, _ty: Option<&Token>, initializer: Option<&BoxedASTNode>, _constant: bool, _public: bool) {
+        walk_var_decl(self, initializer)
+    }
+
+    fn visit_variable(&mut self, _node: &BoxedASTNode, _name: &Token) {}
+
+    fn visit_test(&mut self, _node: &BoxedASTNode, _name: &Token, body: &[BoxedASTNode]) {
+        walk_test(self, body)
+    }
+
+    fn visit_assert(&mut self, _node: &BoxedASTNode, _keyword: &Token, condition: &BoxedASTNode, message: Option<&BoxedASTNode>) {
+        walk_assert(self, condition, message)
+    }
+}
+
+/// Visit every node in a program.
+pub fn walk_ast<V: Visitor>(visitor: &mut V, ast: &[BoxedASTNode]) {
+    for node in ast {
+        visitor.visit_node(node);
+    }
+}
+
+/// Dispatch node to a matching `visit_*` method.
+pub fn walk_node<V: Visitor>(visitor: &mut V, node: &BoxedASTNode) {
+    let n = node.borrow();
+    match **n {
+        ASTNode::Binary { ref left, ref operator, ref right } => visitor.visit_binary(node, left, operator, right),
+        ASTNode::Logical { ref left, ref operator, ref right } => visitor.visit_logical(node, left, operator, right),
+        ASTNode::Unary { ref operator, ref right } => visitor.visit_unary(node, operator, right),
+        ASTNode::Literal { ref value } => visitor.visit_literal(node, value),
+        ASTNode::Grouping { ref expr } => visitor.visit_grouping(node, expr),
+        ASTNode::VarDecl { ref name, ref ty, ref initializer, constant, public } => visitor.visit_var_decl(node, name, ty.as_ref(), initializer.as_ref(), constant, public),
+        ASTNode::Variable { ref name } => visitor.visit_variable(node, name),
+        ASTNode::Test { ref name, ref body, .. } => visitor.visit_test(node, name, body),
+        ASTNode::Assert { ref keyword, ref condition, ref message } => visitor.visit_assert(node, keyword, condition, message.as_ref()),
+    }
+}
+
+pub fn walk_binary<V: Visitor>(visitor: &mut V, left: &BoxedASTNode, right: &BoxedASTNode) {
+    visitor.visit_node(left);
+    visitor.visit_node(right);
+}
+
+pub fn walk_logical<V: Visitor>(visitor: &mut V, left: &BoxedASTNode, right: &BoxedASTNode) {
+    visitor.visit_node(left);
+    visitor.visit_node(right);
+}
+
+pub fn walk_unary<V: Visitor>(visitor: &mut V, right: &BoxedASTNode) {
+    visitor.visit_node(right);
+}
+
+pub fn walk_grouping<V: Visitor>(visitor: &mut V, expr: &BoxedASTNode) {
+    visitor.visit_node(expr);
+}
+
+pub fn walk_var_decl<V: Visitor>(visitor: &mut V, initializer: Option<&BoxedASTNode>) {
+    if let Some(initializer) = initializer {
+        visitor.visit_node(initializer);
+    }
+}
+
+pub fn walk_test<V: Visitor>(visitor: &mut V, body: &[BoxedASTNode]) {
+    walk_ast(visitor, body);
+}
+
+pub fn walk_assert<V: Visitor>(visitor: &mut V, condition: &BoxedASTNode, message: Option<&BoxedASTNode>) {
+    visitor.visit_node(condition);
+    if let Some(message) = message {
+        visitor.visit_node(message);
+    }
+}
+
+/// AST visitor that can modify nodes.
+/// Node itself is mutably borrowed during the visit, so only its metadata is passed along.
+pub trait VisitorMut: Sized {
+    fn visit_node_mut(&mut self, node: &BoxedASTNode) {
+        walk_node_mut(self, node)
+    }
+
+    fn visit_binary_mut(&mut self, _meta: &MetaContainer, left: &mut BoxedASTNode, _operator: &mut Token, right: &mut BoxedASTNode) {
+        walk_binary_mut(self, left, right)
+    }
+
+    fn visit_logical_mut(&mut self, _meta: &MetaContainer, left: &mut BoxedASTNode, _operator: &mut Token, right: &mut BoxedASTNode) {
+        walk_logical_mut(self, left, right)
+    }
+
+    fn visit_unary_mut(&mut self, _meta: &MetaContainer, _operator: &mut Token, right: &mut BoxedASTNode) {
+        walk_unary_mut(self, right)
+    }
+
+    fn visit_literal_mut(&mut self, _meta: &MetaContainer, _value: &mut Token) {}
+
+    fn visit_grouping_mut(&mut self, _meta: &MetaContainer, expr: &mut BoxedASTNode) {
+        walk_grouping_mut(self, expr)
+    }
+
+    fn visit_var_decl_mut(&mut self, _meta: &MetaContainer, _name: &mut Token, _ty: &mut Option<Token>, initializer: &mut Option<BoxedASTNode>, _constant: &mut bool, _public: &mut bool) {
+        walk_var_decl_mut(self, initializer)
+    }
+
+    fn visit_variable_mut(&mut self, _meta: &MetaContainer, _name: &mut Token) {}
+
+    fn visit_test_mut(&mut self, _meta: &MetaContainer, _name: &mut Token, body: &mut Vec<BoxedASTNode>) {
+        walk_test_mut(self, body)
+    }
+
+    fn visit_assert_mut(&mut self, _meta: &MetaContainer, _keyword: &mut Token, condition: &mut BoxedASTNode, message: &mut Option<BoxedASTNode>) {
+        walk_assert_mut(self, condition, message)
+    }
+}
+
+/// Mutably visit every node in a program.
+pub fn walk_ast_mut<V: VisitorMut>(visitor: &mut V, ast: &[BoxedASTNode]) {
+    for node in ast {
+        visitor.visit_node_mut(node);
+    }
+}
+
+/// Dispatch node to a matching `visit_*_mut` method.
+pub fn walk_node_mut<V: VisitorMut>(visitor: &mut V, node: &BoxedASTNode) {
+    let meta = &node.meta;
+    let mut n = node.borrow_mut();
+    match **n {
+        ASTNode::Binary { ref mut left, ref mut operator, ref mut right } => visitor.visit_binary_mut(meta, left, operator, right),
+        ASTNode::Logical { ref mut left, ref mut operator, ref mut right } => visitor.visit_logical_mut(meta, left, operator, right),
+        ASTNode::Unary { ref mut operator, ref mut right } => visitor.visit_unary_mut(meta, operator, right),
+        ASTNode::Literal { ref mut value } => visitor.visit_literal_mut(meta, value),
+        ASTNode::Grouping { ref mut expr } => visitor.visit_grouping_mut(meta, expr),
+        ASTNode::VarDecl { ref mut name, ref mut ty, ref mut initializer, ref mut constant, ref mut public } => visitor.visit_var_decl_mut(meta, name, ty, initializer, constant, public),
+        ASTNode::Variable { ref mut name } => visitor.visit_variable_mut(meta, name),
+        ASTNode::Test { ref mut name, ref mut body, .. } => visitor.visit_test_mut(meta, name, body),
+        ASTNode::Assert { ref mut keyword, ref mut condition, ref mut message } => visitor.visit_assert_mut(meta, keyword, condition, message),
+    }
+}
+
+pub fn walk_binary_mut<V: VisitorMut>(visitor: &mut V, left: &mut BoxedASTNode, right: &mut BoxedASTNode) {
+    visitor.visit_node_mut(left);
+    visitor.visit_node_mut(right);
+}
+
+pub fn walk_logical_mut<V: VisitorMut>(visitor: &mut V, left: &mut BoxedASTNode, right: &mut BoxedASTNode) {
+    visitor.visit_node_mut(left);
+    visitor.visit_node_mut(right);
+}
+
+pub fn walk_unary_mut<V: VisitorMut>(visitor: &mut V, right: &mut BoxedASTNode) {
+    visitor.visit_node_mut(right);
+}
+
+pub fn walk_grouping_mut<V: VisitorMut>(visitor: &mut V, expr: &mut BoxedASTNode) {
+    visitor.visit_node_mut(expr);
+}
+
+pub fn walk_var_decl_mut<V: VisitorMut>(visitor: &mut V, initializer: &mut Option<BoxedASTNode>) {
+    if let Some(initializer) = initializer {
+        visitor.visit_node_mut(initializer);
+    }
+}
+
+pub fn walk_test_mut<V: VisitorMut>(visitor: &mut V, body: &mut [BoxedASTNode]) {
+    walk_ast_mut(visitor, body);
+}
+
+pub fn walk_assert_mut<V: VisitorMut>(visitor: &mut V, condition: &mut BoxedASTNode, message: &mut Option<BoxedASTNode>) {
+    visitor.visit_node_mut(condition);
+    if let Some(message) = message {
+        visitor.visit_node_mut(message);
+    }
+}
+
+/// Rewrites AST bottom-up, replacing nodes with whatever `fold_node` returns.
+pub trait Folder: Sized {
+    /// Returns node that should take place of `node`.
+    /// Default implementation folds children and keeps the node.
+    fn fold_node(&mut self, node: BoxedASTNode) -> BoxedASTNode {
+        fold_children(self, &node);
+        node
+    }
+}
+
+/// Fold every top-level node of a program, replacing it in place.
+pub fn fold_ast<F: Folder>(folder: &mut F, ast: &mut [BoxedASTNode]) {
+    for node in ast.iter_mut() {
+        *node = folder.fold_node(node.clone());
+    }
+}
+
+/// Fold all children of a node, replacing them inside of it.
+pub fn fold_children<F: Folder>(folder: &mut F, node: &BoxedASTNode) {
+    let mut n = node.borrow_mut();
+    match **n {
+        ASTNode::Binary { ref mut left, ref mut right, .. }
+        | ASTNode::Logical { ref mut left, ref mut right, .. } => {
+            *left = folder.fold_node(left.clone());
+            *right = folder.fold_node(right.clone());
+        },
+        ASTNode::Unary { ref mut right, .. } => *right = folder.fold_node(right.clone()),
+        ASTNode::Grouping { ref mut expr } => *expr = folder.fold_node(expr.clone()),
+        ASTNode::VarDecl { initializer: Some(ref mut initializer), .. } => {
+            *initializer = folder.fold_node(initializer.clone());
+        },
+        ASTNode::Test { ref mut body, .. } => fold_ast(folder, body),
+        ASTNode::Assert { ref mut condition, ref mut message, .. } => {
+            *condition = folder.fold_node(condition.clone());
+            if let Some(message) = message {
+                *message = folder.fold_node(message.clone());
+            }
+        },
+        ASTNode::VarDecl { initializer: None, .. }
+        | ASTNode::Literal { .. }
+        | ASTNode::Variable { .. } => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::ast::ASTNodeVecExt, util::testing::parse};
+
+    use super::*;
+
+    /// Counts literals in the whole program.
+    struct LiteralCounter(usize);
+
+    impl Visitor for LiteralCounter {
+        fn visit_literal(&mut self, _node: &BoxedASTNode, _value: &Token) {
+            self.0 += 1;
+        }
+    }
+
+    /// Renames every variable to `renamed`.
+    struct Renamer;
+
+    impl VisitorMut for Renamer {
+        fn visit_variable_mut(&mut self, _meta: &MetaContainer, name: &mut Token) {
+            name.lexeme = "renamed".to_string();
+        }
+    }
+
+    /// Removes all grouping nodes.
+    struct Ungroup;
+
+    impl Folder for Ungroup {
+        fn fold_node(&mut self, node: BoxedASTNode) -> BoxedASTNode {
+            fold_children(self, &node);
+            let inner = match **node.borrow() {
+                ASTNode::Grouping { ref expr } => expr.clone(),
+                _ => return node.clone(),
+            };
+            inner
+        }
+    }
+
+    #[test]
+    fn test_visitors() {
+        let ast = parse("let x = 1 + (2 * y); -x");
+
+        let mut counter = LiteralCounter(0);
+        walk_ast(&mut counter, &ast);
+        assert_eq!(counter.0, 2);
+
+        walk_ast_mut(&mut Renamer, &ast);
+        assert_eq!(ast.display(0), "{\n    (letvardecl x = (+ 1 ((* 2 (var renamed)))))\n    (- (var renamed))\n}\n");
+    }
+
+    #[test]
+    fn test_folder() {
+        let mut ast = parse("((1 + (2)))");
+        fold_ast(&mut Ungroup, &mut ast);
+        assert_eq!(ast.display(0), "{\n    (+ 1 2)\n}\n");
+    }
+}
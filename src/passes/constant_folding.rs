@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+
+use crate::{
+    parser::{ast::{ASTNode, BoxedASTNode}, visitor::{fold_ast, fold_children, Folder}},
+    scanner::{Token, TokenType},
+    util::{diagnostic::Diagnostic, unified_error, HastyError},
+};
+
+use super::{manager::MetaKey, resolver::BindingId, Pass};
+
+/// Value known at compile time.
+/// Also attached to declarations with constant initializers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Integer(i64),
+    Floating(f64),
+    String(String),
+    Bool(bool),
+    Nil,
+}
+
+impl Constant {
+    /// Reads constant from a literal token.
+    pub fn from_token(token: &Token) -> Option<Self> {
+        Some(match token.token_type {
+            TokenType::INTEGER => Constant::Integer(token.lexeme.parse().ok()?),
+            TokenType::FLOATING => Constant::Floating(token.lexeme.parse().ok()?),
+            TokenType::STRING => Constant::String(token.data.as_ref()?.downcast_ref::<String>()?.clone()),
+            TokenType::TRUE => Constant::Bool(true),
+            TokenType::FALSE => Constant::Bool(false),
+            TokenType::NIL => Constant::Nil,
+            _ => return None,
+        })
+    }
+
+    /// Creates literal token with this value, placed at the position of `at`.
+    /// Strings are stored unescaped in token data, which backends quote themselves,
+    /// the lexeme is only used to display the AST.
+    pub fn to_token(&self, at: &Token) -> Token {
+        let (token_type, lexeme) = match self {
+            Constant::Integer(value) => (TokenType::INTEGER, value.to_string()),
+            Constant::Floating(value) => (TokenType::FLOATING, format!("{:?}", value)),
+            Constant::String(value) => (TokenType::STRING, format!("\"{}\"", value)),
+            Constant::Bool(true) => (TokenType::TRUE, "true".to_string()),
+            Constant::Bool(false) => (TokenType::FALSE, "false".to_string()),
+            Constant::Nil => (TokenType::NIL, "nil".to_string()),
+        };
+
+        let token = Token::new(token_type, lexeme, at.line, at.start);
+        match self {
+            Constant::String(value) => token.with_data(value.clone()),
+            _ => token,
+        }
+    }
+
+    /// Truthiness of a value, same as in javascript.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Constant::Integer(value) => *value != 0,
+            Constant::Floating(value) => *value != 0.0 && !value.is_nan(),
+            Constant::String(value) => !value.is_empty(),
+            Constant::Bool(value) => *value,
+            Constant::Nil => false,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Constant::Integer(value) => Some(*value as f64),
+            Constant::Floating(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Marks `const` declarations whose value was propagated into at least one use.
+#[derive(Debug)]
+pub struct Propagated;
+
+#[derive(Debug)]
+pub enum ConstantFoldingErrorTy {
+    /// Division where the right operand is a constant zero.
+    DivisionByZero,
+}
+
+#[derive(Debug)]
+pub struct ConstantFoldingError {
+    pub ty: ConstantFoldingErrorTy,
+    pub token: Token,
+}
+
+impl HastyError for ConstantFoldingError {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("CONSTANT FOLDING", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match self.ty {
+            ConstantFoldingErrorTy::DivisionByZero => "Division by zero.",
+        }.to_string()
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        match self.ty {
+            ConstantFoldingErrorTy::DivisionByZero => Diagnostic::error(self.get_error_description())
+                .with_code("E0401")
+                .with_primary(&self.token, "right operand is always zero"),
+        }
+    }
+}
+
+/// Result of folding a single operation.
+enum Folded {
+    /// Operation can not be evaluated at compile time.
+    Unknown,
+    /// Node evaluates to a constant.
+    Constant(Constant),
+    /// Node evaluates to one of its operands (short-circuit).
+    Operand(BoxedASTNode),
+}
+
+/// Optimization pass that evaluates expressions with constant operands
+/// and replaces uses of `const` bindings with their values.
+pub struct ConstantFolder {
+    /// Values of `const` bindings and their declarations.
+    constants: HashMap<BindingId, (Constant, BoxedASTNode)>,
+    errors: Vec<ConstantFoldingError>,
+}
+
+impl Default for ConstantFolder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstantFolder {
+    pub fn new() -> Self {
+        Self {
+            constants: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Constant value of an already folded node.
+    fn constant(node: &BoxedASTNode) -> Option<Constant> {
+        match **node.borrow() {
+            ASTNode::Literal { ref value } => Constant::from_token(value),
+            _ => None,
+        }
+    }
+
+    fn fold_binary(&mut self, left: &Constant, operator: &Token, right: &Constant) -> Option<Constant> {
+        use Constant::*;
+
+        let result = match (operator.token_type.clone(), left, right) {
+            (TokenType::SLASH, _, right) if right.as_f64() == Some(0.0) => {
+                self.errors.push(ConstantFoldingError {
+                    ty: ConstantFoldingErrorTy::DivisionByZero,
+                    token: operator.clone(),
+                });
+                return None;
+            },
+
+            (TokenType::PLUS, String(l), String(r)) => String(format!("{}{}", l, r)),
+
+            // Integer arithmetic, which is not folded on overflow.
+            (TokenType::PLUS, Integer(l), Integer(r)) => Integer(l.checked_add(*r)?),
+            (TokenType::MINUS, Integer(l), Integer(r)) => Integer(l.checked_sub(*r)?),
+            (TokenType::STAR, Integer(l), Integer(r)) => Integer(l.checked_mul(*r)?),
+            // Division has the same result as in javascript, so `7 / 2` is `3.5`.
+            (TokenType::SLASH, Integer(l), Integer(r)) if l.checked_rem(*r) == Some(0) => Integer(l.checked_div(*r)?),
+
+            (TokenType::PLUS | TokenType::MINUS | TokenType::STAR | TokenType::SLASH, l, r) => {
+                let (l, r) = (l.as_f64()?, r.as_f64()?);
+                Floating(match operator.token_type {
+                    TokenType::PLUS => l + r,
+                    TokenType::MINUS => l - r,
+                    TokenType::STAR => l * r,
+                    _ => l / r,
+                })
+            },
+
+            (TokenType::EQUAL_EQUAL, l, r) => Bool(Self::strict_equals(l, r)),
+            (TokenType::BANG_EQUAL, l, r) => Bool(!Self::strict_equals(l, r)),
+
+            (TokenType::LESS | TokenType::LESS_EQUAL | TokenType::GREATER | TokenType::GREATER_EQUAL, l, r) => {
+                let ordering = match (l, r) {
+                    (String(l), String(r)) => l.partial_cmp(r)?,
+                    (l, r) => l.as_f64()?.partial_cmp(&r.as_f64()?)?,
+                };
+                Bool(match operator.token_type {
+                    TokenType::LESS => ordering.is_lt(),
+                    TokenType::LESS_EQUAL => ordering.is_le(),
+                    TokenType::GREATER => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                })
+            },
+
+            _ => return None,
+        };
+
+        // Results that can not be written as a literal are left for runtime.
+        match result {
+            Floating(value) if !value.is_finite() => None,
+            result => Some(result),
+        }
+    }
+
+    /// Equality as in javascript `===`.
+    fn strict_equals(left: &Constant, right: &Constant) -> bool {
+        match (left.as_f64(), right.as_f64()) {
+            (Some(l), Some(r)) => l == r,
+            _ => left == right,
+        }
+    }
+
+    fn fold_unary(operator: &Token, right: &Constant) -> Option<Constant> {
+        match (&operator.token_type, right) {
+            (TokenType::BANG, right) => Some(Constant::Bool(!right.is_truthy())),
+            (TokenType::MINUS, Constant::Integer(value)) => Some(Constant::Integer(value.checked_neg()?)),
+            (TokenType::MINUS, Constant::Floating(value)) => Some(Constant::Floating(-value)),
+            _ => None,
+        }
+    }
+
+    /// Evaluates node whose children are already folded.
+    fn fold(&mut self, node: &BoxedASTNode) -> Folded {
+        let n = node.borrow();
+        let folded = match **n {
+            ASTNode::Binary { ref left, ref operator, ref right } => {
+                match (Self::constant(left), Self::constant(right)) {
+                    (Some(l), Some(r)) => self.fold_binary(&l, operator, &r),
+                    _ => None,
+                }
+            },
+            ASTNode::Logical { ref left, ref operator, ref right } => {
+                // Only the left operand has to be known to short-circuit.
+                let Some(l) = Self::constant(left) else { return Folded::Unknown };
+                let short_circuits = match operator.token_type {
+                    TokenType::AND => !l.is_truthy(),
+                    _ => l.is_truthy(),
+                };
+                if short_circuits { Some(l) } else { return Folded::Operand(right.clone()) }
+            },
+            ASTNode::Unary { ref operator, ref right } => {
+                Self::constant(right).and_then(|r| Self::fold_unary(operator, &r))
+            },
+            ASTNode::Grouping { ref expr } => Self::constant(expr),
+            ASTNode::Variable { .. } => {
+                let id = node.meta.get::<BindingId>();
+                id.and_then(|id| self.constants.get(&id)).map(|(value, declaration)| {
+                    declaration.meta.insert(Propagated);
+                    value.clone()
+                })
+            },
+            ASTNode::VarDecl { ref initializer, constant, .. } => {
+                if let Some(value) = initializer.as_ref().and_then(Self::constant) {
+                    node.meta.insert(value.clone());
+                    if let (true, Some(id)) = (constant, node.meta.get::<BindingId>()) {
+                        self.constants.insert(*id, (value, node.clone()));
+                    }
+                }
+                return Folded::Unknown;
+            },
+            ASTNode::Literal { .. } | ASTNode::Test { .. } | ASTNode::Assert { .. } => return Folded::Unknown,
+        };
+
+        folded.map_or(Folded::Unknown, Folded::Constant)
+    }
+}
+
+impl Folder for ConstantFolder {
+    fn fold_node(&mut self, node: BoxedASTNode) -> BoxedASTNode {
+        fold_children(self, &node);
+
+        match self.fold(&node) {
+            Folded::Unknown => node,
+            Folded::Operand(operand) => operand,
+            Folded::Constant(value) => {
+                let token = value.to_token(&node.borrow().position());
+                // Rewrite in place, so metadata and other references stay valid.
+                **node.borrow_mut() = ASTNode::Literal { value: token };
+                node
+            },
+        }
+    }
+}
+
+impl Pass for ConstantFolder {
+    type Error = Vec<ConstantFoldingError>;
+    type AdditionalData = ();
+
+    fn name(&self) -> &'static str {
+        "constant-folding"
+    }
+
+    fn requires(&self) -> Vec<MetaKey> {
+        vec![MetaKey::of::<BindingId>()]
+    }
+
+    fn produces(&self) -> Vec<MetaKey> {
+        vec![MetaKey::of::<Constant>()]
+    }
+
+    fn process(&mut self, ast: &mut Vec<BoxedASTNode>) -> Result<Self::AdditionalData, Self::Error> {
+        fold_ast(self, ast);
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::ast::ASTNodeVecExt, util::testing::resolve};
+
+    use super::*;
+
+    #[track_caller]
+    fn fold(code: &str) -> Result<String, Vec<ConstantFoldingError>> {
+        let mut ast = resolve(code);
+        ConstantFolder::new().process(&mut ast)?;
+        Ok(ast.display(0))
+    }
+
+    #[test]
+    fn test_fold_expressions() {
+        assert_eq!(fold("1 + 2 * 3").unwrap(), "{\n    7\n}\n");
+        assert_eq!(fold("(1 + 2) / 4 == 0.75").unwrap(), "{\n    true\n}\n");
+        assert_eq!(fold("\"a\" + \"b\" < \"b\"").unwrap(), "{\n    true\n}\n");
+        assert_eq!(fold("-(2 - 5) > 2 && !false").unwrap(), "{\n    true\n}\n");
+        assert_eq!(fold("let x = 1; false || x").unwrap(), "{\n    (letvardecl x = 1)\n    (var x)\n}\n");
+        assert_eq!(fold("(-9223372036854775807 - 1) / -1").unwrap(), "{\n    9.223372036854776e18\n}\n");
+    }
+
+    #[test]
+    fn test_fold_strings() {
+        let mut ast = resolve("\"a\\\" + \"b\nc\"");
+        ConstantFolder::new().process(&mut ast).unwrap();
+        let mut result = Vec::new();
+        crate::compiler::Compiler::new().compile_ast(&ast, &mut result).unwrap();
+        assert_eq!(String::from_utf8(result).unwrap(), "\"a\\\\b\\nc\";\n");
+    }
+
+    #[test]
+    fn test_propagate_constants() {
+        assert_eq!(
+            fold("const a = 2 * 3; let b = 1; a + 1 + b").unwrap(),
+            "{\n    (constvardecl a = 6)\n    (letvardecl b = 1)\n    (+ 7 (var b))\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let errors = fold("const zero = 1 - 1; 2 / zero").unwrap_err();
+        assert!(matches!(errors[0].ty, ConstantFoldingErrorTy::DivisionByZero));
+    }
+}
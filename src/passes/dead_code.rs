@@ -0,0 +1,255 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    parser::{ast::{ASTNode, BoxedASTNode}, visitor::{walk_ast, Visitor}},
+    scanner::Token,
+    util::{diagnostic::Diagnostic, unified_error, HastyError},
+};
+
+use super::{constant_folding::{Constant, Propagated}, manager::MetaKey, resolver::BindingId, Pass};
+
+#[derive(Debug, Clone)]
+pub enum DeadCodeWarningTy {
+    /// Variable is declared, but never read.
+    UnusedVariable,
+    /// Expression statement without side effects.
+    UnusedExpression,
+    /// Statement that can never run, because an assertion before it always fails.
+    UnreachableCode,
+}
+
+/// Warning about code that was removed, because it has no effect.
+#[derive(Debug, Clone)]
+pub struct DeadCodeWarning {
+    pub ty: DeadCodeWarningTy,
+    pub token: Token,
+}
+
+impl HastyError for DeadCodeWarning {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("DEAD CODE", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match self.ty {
+            DeadCodeWarningTy::UnusedVariable => format!("Variable '{}' is never used.", self.token.lexeme),
+            DeadCodeWarningTy::UnusedExpression => "Result of this expression is never used.".to_string(),
+            DeadCodeWarningTy::UnreachableCode => "This code is never executed.".to_string(),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::warning(self.get_error_description());
+        match self.ty {
+            DeadCodeWarningTy::UnusedVariable => diagnostic.with_code("W0001")
+                .with_primary(&self.token, "never read")
+                .with_help("remove the declaration, or add 'pub' to export it"),
+            DeadCodeWarningTy::UnusedExpression => diagnostic.with_code("W0002")
+                .with_primary(&self.token, "value is discarded")
+                .with_note("expression statements without side effects are removed"),
+            DeadCodeWarningTy::UnreachableCode => diagnostic.with_code("W0003")
+                .with_primary(&self.token, "unreachable")
+                .with_note("the assertion before it always fails"),
+        }
+    }
+}
+
+/// Counts how many times each binding is read.
+#[derive(Default)]
+pub(crate) struct UsageCounter(pub(crate) HashMap<BindingId, usize>);
+
+impl UsageCounter {
+    /// Counts reads which are not in initializers of `removable` declarations that are never read,
+    /// repeated until declarations that are only read by such declarations are not counted either.
+    pub(crate) fn live(ast: &[BoxedASTNode], removable: impl Fn(&BoxedASTNode) -> bool) -> Self {
+        let mut dead = HashSet::new();
+        loop {
+            let mut usages = Self::default();
+            for node in ast.iter().filter(|node| !node.meta.get::<BindingId>().is_some_and(|id| dead.contains(&*id))) {
+                usages.visit_node(node);
+            }
+
+            let before = dead.len();
+            for node in ast.iter().filter(|node| removable(node)) {
+                if let Some(id) = node.meta.get::<BindingId>().filter(|id| !usages.0.contains_key(id)) {
+                    dead.insert(*id);
+                }
+            }
+            if dead.len() == before {
+                return usages;
+            }
+        }
+    }
+}
+
+impl Visitor for UsageCounter {
+    fn visit_variable(&mut self, node: &BoxedASTNode, _name: &Token) {
+        if let Some(id) = node.meta.get::<BindingId>() {
+            *self.0.entry(*id).or_default() += 1;
+        }
+    }
+}
+
+/// Optimization pass that removes statements without any effect:
+/// pure expression statements, private declarations that are never read
+/// and statements in tests after an assertion that always fails.
+/// Should run after constant folding, so that propagated constants are not counted as uses
+/// and constant assertions are known.
+pub struct DeadCodeEliminator {
+    warnings: Vec<DeadCodeWarning>,
+}
+
+impl Default for DeadCodeEliminator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeadCodeEliminator {
+    pub fn new() -> Self {
+        Self {
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Whether evaluating node can have any observable effect.
+    pub fn is_pure(node: &BoxedASTNode) -> bool {
+        match **node.borrow() {
+            ASTNode::Binary { ref left, ref right, .. }
+            | ASTNode::Logical { ref left, ref right, .. } => Self::is_pure(left) && Self::is_pure(right),
+            ASTNode::Unary { ref right, .. } => Self::is_pure(right),
+            ASTNode::Grouping { ref expr } => Self::is_pure(expr),
+            ASTNode::Literal { .. } | ASTNode::Variable { .. } => true,
+            ASTNode::VarDecl { ref initializer, .. } => initializer.as_ref().is_none_or(Self::is_pure),
+            // Tests are kept for the test runner, and failing assertions are observable.
+            ASTNode::Test { .. } | ASTNode::Assert { .. } => false,
+        }
+    }
+
+    /// Removes statements of a test that come after an assertion which always fails.
+    fn remove_unreachable(&mut self, node: &BoxedASTNode) {
+        let mut n = node.borrow_mut();
+        let ASTNode::Test { ref mut body, .. } = **n else { return };
+        let Some(failing) = body.iter().position(Self::always_fails) else { return };
+
+        if let Some(first) = body.get(failing + 1) {
+            let token = first.borrow().position();
+            self.warnings.push(DeadCodeWarning { ty: DeadCodeWarningTy::UnreachableCode, token });
+            body.truncate(failing + 1);
+        }
+    }
+
+    /// Whether node is an assertion of a constant falsy condition.
+    fn always_fails(node: &BoxedASTNode) -> bool {
+        let ASTNode::Assert { ref condition, .. } = **node.borrow() else { return false };
+        let ASTNode::Literal { ref value } = **condition.borrow() else { return false };
+        Constant::from_token(value).is_some_and(|value| !value.is_truthy())
+    }
+
+    /// Decides whether top-level statement should be kept, reporting it if it is not.
+    fn keep(&mut self, node: &BoxedASTNode, usages: &UsageCounter) -> bool {
+        let n = node.borrow();
+        let warning = match **n {
+            ASTNode::VarDecl { public: true, .. } => return true,
+            ASTNode::VarDecl { ref name, .. } => {
+                let Some(id) = node.meta.get::<BindingId>() else { return true };
+                if usages.0.contains_key(&id) || !Self::is_pure(node) { return true; }
+                // Constants propagated into every use are removed silently.
+                if node.meta.get::<Propagated>().is_some() { return false; }
+                DeadCodeWarning { ty: DeadCodeWarningTy::UnusedVariable, token: name.clone() }
+            },
+            _ => {
+                if !Self::is_pure(node) { return true; }
+                DeadCodeWarning { ty: DeadCodeWarningTy::UnusedExpression, token: n.position() }
+            },
+        };
+
+        self.warnings.push(warning);
+        false
+    }
+}
+
+impl Pass for DeadCodeEliminator {
+    /// Never returned, as elimination itself can not fail.
+    type Error = Vec<DeadCodeWarning>;
+    /// Warnings about removed code.
+    type AdditionalData = Vec<DeadCodeWarning>;
+
+    fn name(&self) -> &'static str {
+        "dead-code-elimination"
+    }
+
+    fn requires(&self) -> Vec<MetaKey> {
+        vec![MetaKey::of::<BindingId>(), MetaKey::of::<Constant>()]
+    }
+
+    fn process(&mut self, ast: &mut Vec<BoxedASTNode>) -> Result<Self::AdditionalData, Self::Error> {
+        // Unreachable code is removed first, so that it does not count as a use.
+        for node in ast.iter() {
+            self.remove_unreachable(node);
+        }
+
+        // Removing a declaration can make the ones it reads unused too.
+        loop {
+            let mut usages = UsageCounter::default();
+            walk_ast(&mut usages, ast);
+
+            let before = ast.len();
+            ast.retain(|node| self.keep(node, &usages));
+            if ast.len() == before { break; }
+        }
+
+        Ok(std::mem::take(&mut self.warnings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::ast::ASTNodeVecExt, passes::constant_folding::ConstantFolder, util::testing::resolve};
+
+    use super::*;
+
+    #[track_caller]
+    fn eliminate(code: &str) -> (String, Vec<DeadCodeWarning>) {
+        let mut ast = resolve(code);
+        ConstantFolder::new().process(&mut ast).unwrap();
+        let warnings = DeadCodeEliminator::new().process(&mut ast).unwrap();
+        (ast.display(0), warnings)
+    }
+
+    #[test]
+    fn test_remove_unused() {
+        let (ast, warnings) = eliminate("const a = 1; let b = a + 1; let c = b; 1 + 2");
+        assert_eq!(ast, "{\n}\n");
+
+        // `b` was only used by unused `c`, and `a` was propagated by constant folding.
+        assert_eq!(warnings.len(), 3);
+        assert!(matches!(warnings[0].ty, DeadCodeWarningTy::UnusedVariable));
+        assert_eq!(warnings[0].token.lexeme, "c");
+        assert!(matches!(warnings[1].ty, DeadCodeWarningTy::UnusedExpression));
+        assert_eq!(warnings[2].token.lexeme, "b");
+
+        let (ast, _) = eliminate("pub let x = 1;");
+        assert_eq!(ast, "{\n    (pub letvardecl x = 1)\n}\n");
+
+        let (ast, warnings) = eliminate("let x = 1; let y = x;");
+        assert_eq!(ast, "{\n}\n");
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_remove_unreachable() {
+        let (ast, warnings) = eliminate("let x = 1; let z = 2; test fn fails() { assert(x == 1) assert(1 > 2, \"never\") let y = z; assert(y) }");
+        assert_eq!(ast, "{\n    (letvardecl x = 1)\n    (test fails (assert (== (var x) 1)) (assert false \"never\"))\n}\n");
+
+        // `z` was only read by unreachable code.
+        assert_eq!(warnings.len(), 2);
+        assert!(matches!(warnings[0].ty, DeadCodeWarningTy::UnreachableCode));
+        assert_eq!(warnings[0].token.lexeme, "y");
+        assert!(matches!(warnings[1].ty, DeadCodeWarningTy::UnusedVariable));
+        assert_eq!(warnings[1].token.lexeme, "z");
+    }
+}
@@ -0,0 +1,604 @@
+use std::{collections::HashMap, fmt::Display, path::{Path, PathBuf}};
+
+use crate::{
+    parser::ast::{ASTNode, BoxedASTNode},
+    scanner::{Token, TokenType},
+    util::{diagnostic::{Diagnostic, Severity, Span}, unified_error, HastyError},
+};
+
+use super::{
+    constant_folding::Constant,
+    dead_code::{DeadCodeEliminator, UsageCounter},
+    manager::MetaKey,
+    resolver::BindingId,
+    Pass,
+};
+
+/// What happens when a lint finds something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Lint is not reported.
+    Allow,
+    /// Lint is reported as a warning.
+    Warn,
+    /// Lint is reported as an error, so compilation fails.
+    Deny,
+}
+
+impl LintLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        Some(match level {
+            "allow" => LintLevel::Allow,
+            "warn" => LintLevel::Warn,
+            "deny" => LintLevel::Deny,
+            _ => return None,
+        })
+    }
+}
+
+impl Display for LintLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            LintLevel::Allow => "allow",
+            LintLevel::Warn => "warn",
+            LintLevel::Deny => "deny",
+        })
+    }
+}
+
+/// Description of a single lint.
+#[derive(Debug)]
+pub struct Lint {
+    /// Name used in configuration, like `unused-variable`.
+    pub id: &'static str,
+    /// Diagnostic code, like `W0101`.
+    pub code: &'static str,
+    pub description: &'static str,
+    pub default_level: LintLevel,
+}
+
+pub const UNUSED_VARIABLE: Lint = Lint {
+    id: "unused-variable",
+    code: "W0101",
+    description: "private variable that is never read",
+    default_level: LintLevel::Warn,
+};
+
+pub const SHADOWED_BINDING: Lint = Lint {
+    id: "shadowed-binding",
+    code: "W0102",
+    description: "declaration with the same name as an earlier one",
+    default_level: LintLevel::Allow,
+};
+
+pub const SELF_COMPARISON: Lint = Lint {
+    id: "self-comparison",
+    code: "W0103",
+    description: "comparison of a value to itself",
+    default_level: LintLevel::Warn,
+};
+
+pub const REDUNDANT_PARENTHESES: Lint = Lint {
+    id: "redundant-parentheses",
+    code: "W0104",
+    description: "parentheses that do not change the order of evaluation",
+    default_level: LintLevel::Warn,
+};
+
+pub const CONSTANT_CONDITION: Lint = Lint {
+    id: "constant-condition",
+    code: "W0105",
+    description: "logical operator with a constant left operand",
+    default_level: LintLevel::Warn,
+};
+
+pub const NAMING_CONVENTION: Lint = Lint {
+    id: "naming-convention",
+    code: "W0106",
+    description: "variable not named in camelCase, or constant not in camelCase or UPPERCASE",
+    default_level: LintLevel::Warn,
+};
+
+pub const UNKNOWN_LINT: Lint = Lint {
+    id: "unknown-lint",
+    code: "W0107",
+    description: "lint directive with an unknown lint or level",
+    default_level: LintLevel::Warn,
+};
+
+/// Every available lint.
+pub const LINTS: [&Lint; 7] = [
+    &UNUSED_VARIABLE,
+    &SHADOWED_BINDING,
+    &SELF_COMPARISON,
+    &REDUNDANT_PARENTHESES,
+    &CONSTANT_CONDITION,
+    &NAMING_CONVENTION,
+    &UNKNOWN_LINT,
+];
+
+impl Lint {
+    pub fn find(id: &str) -> Option<&'static Lint> {
+        LINTS.into_iter().find(|lint| lint.id == id)
+    }
+}
+
+/// Levels of lints, overriding their defaults.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<&'static str, LintLevel>,
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the `[lints]` table of a project file (`hasty.toml`),
+    /// where each entry looks like `unused-variable = "deny"`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::new();
+        let mut in_lints = false;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() { continue; }
+
+            if line.starts_with('[') {
+                in_lints = line == "[lints]";
+                continue;
+            }
+            if !in_lints { continue; }
+
+            let error = |message: String| format!("line {}: {}", i + 1, message);
+            let Some((id, level)) = line.split_once('=') else {
+                return Err(error(format!("expected 'lint = \"level\"', found '{}'", line)));
+            };
+            let id = id.trim().trim_matches('"');
+            let level = level.trim().trim_matches('"');
+            let level = LintLevel::parse(level)
+                .ok_or_else(|| error(format!("unknown lint level '{}', expected allow, warn or deny", level)))?;
+            config.set(id, level).map_err(error)?;
+        }
+        Ok(config)
+    }
+
+    /// Nearest project file (`hasty.toml`) in `directory` or any of its ancestors.
+    pub fn find_project_file(directory: &Path) -> Option<PathBuf> {
+        directory.ancestors().map(|dir| dir.join("hasty.toml")).find(|path| path.is_file())
+    }
+
+    /// Sets level of the lint with given id.
+    pub fn set(&mut self, id: &str, level: LintLevel) -> Result<(), String> {
+        let lint = Lint::find(id).ok_or_else(|| format!("unknown lint '{}'", id))?;
+        self.levels.insert(lint.id, level);
+        Ok(())
+    }
+
+    pub fn level(&self, lint: &Lint) -> LintLevel {
+        self.levels.get(lint.id).copied().unwrap_or(lint.default_level)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum LintTy {
+    UnusedVariable,
+    /// Declaration shadows the one in `related`.
+    ShadowedBinding,
+    SelfComparison { result: bool },
+    RedundantParentheses,
+    /// Left operand of a logical operator is always truthy or always falsy.
+    ConstantCondition { truthy: bool, short_circuits: bool },
+    NamingConvention { expected: String },
+    /// Directive names a lint or level that does not exist.
+    UnknownLint { name: String },
+}
+
+impl LintTy {
+    pub fn lint(&self) -> &'static Lint {
+        match self {
+            LintTy::UnusedVariable => &UNUSED_VARIABLE,
+            LintTy::ShadowedBinding => &SHADOWED_BINDING,
+            LintTy::SelfComparison { .. } => &SELF_COMPARISON,
+            LintTy::RedundantParentheses => &REDUNDANT_PARENTHESES,
+            LintTy::ConstantCondition { .. } => &CONSTANT_CONDITION,
+            LintTy::NamingConvention { .. } => &NAMING_CONVENTION,
+            LintTy::UnknownLint { .. } => &UNKNOWN_LINT,
+        }
+    }
+}
+
+/// Problem found by a lint, reported as a warning or an error depending on its level.
+#[derive(Debug, Clone)]
+pub struct LintReport {
+    pub ty: LintTy,
+    pub token: Token,
+    /// Part of the code the report is about.
+    pub span: Span,
+    pub level: LintLevel,
+    pub related: Option<Token>,
+}
+
+impl HastyError for LintReport {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("LINT", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match &self.ty {
+            LintTy::UnusedVariable => format!("Variable '{}' is never used.", self.token.lexeme),
+            LintTy::ShadowedBinding => format!("Declaration of '{}' shadows an earlier one.", self.token.lexeme),
+            LintTy::SelfComparison { result } => format!("Comparison of a value to itself is always {}.", result),
+            LintTy::RedundantParentheses => "Unnecessary parentheses.".to_string(),
+            LintTy::ConstantCondition { truthy, .. } => {
+                format!("Left operand of '{}' is always {}.", self.token.lexeme, if *truthy { "truthy" } else { "falsy" })
+            },
+            LintTy::NamingConvention { expected } => format!("'{}' should be named '{}'.", self.token.lexeme, expected),
+            LintTy::UnknownLint { name } => format!("Unknown lint or level '{}'.", name),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        let lint = self.ty.lint();
+        let severity = if self.level == LintLevel::Deny { Severity::Error } else { Severity::Warning };
+        let diagnostic = Diagnostic::new(severity, self.get_error_description()).with_code(lint.code);
+
+        let diagnostic = match &self.ty {
+            LintTy::UnusedVariable => diagnostic.with_primary(self.span, "never read")
+                .with_help("remove the declaration, add 'pub' to export it, or start the name with '_'"),
+            LintTy::ShadowedBinding => {
+                let diagnostic = diagnostic.with_primary(self.span, "shadows the earlier declaration");
+                match &self.related {
+                    Some(related) => diagnostic.with_secondary(related, "first declared here"),
+                    None => diagnostic,
+                }
+            },
+            LintTy::SelfComparison { result } => diagnostic.with_primary(self.span, format!("always {}", result))
+                .with_note("unless the value is NaN"),
+            LintTy::RedundantParentheses => diagnostic.with_primary(self.span, "parentheses around this are not needed"),
+            LintTy::ConstantCondition { truthy, short_circuits } => {
+                let diagnostic = diagnostic.with_primary(self.span, if *truthy { "always truthy" } else { "always falsy" });
+                if *short_circuits {
+                    diagnostic.with_note("right operand is never evaluated")
+                } else {
+                    diagnostic.with_note("result is always the right operand")
+                }
+            },
+            LintTy::NamingConvention { expected } => diagnostic.with_primary(self.span, "does not follow the naming convention")
+                .with_suggestion("rename it", self.span, expected.clone()),
+            LintTy::UnknownLint { .. } => diagnostic.with_primary(self.span, "unknown")
+                .with_help("run 'hasty lints' to list available lints"),
+        };
+        diagnostic.with_note(format!("lint '{}' is set to '{}'", lint.id, self.level))
+    }
+}
+
+/// Checks the program for suspicious code and style problems.
+///
+/// Levels come from `LintConfig` and can be changed in comments:
+/// `//! lint: deny(self-comparison)` applies to the whole file and
+/// `// lint: allow(unused-variable, naming-convention)` to the statement after it,
+/// or to the whole test if it is inside of one.
+/// Should run before constant folding, so that it sees the code as written.
+pub struct Linter {
+    config: LintConfig,
+    comments: Vec<Token>,
+    /// Levels for the statement being linted.
+    levels: LintConfig,
+    reports: Vec<LintReport>,
+}
+
+impl Linter {
+    pub fn new(config: LintConfig) -> Self {
+        Self {
+            levels: config.clone(),
+            config,
+            comments: Vec::new(),
+            reports: Vec::new(),
+        }
+    }
+
+    /// Comments of the program (see `Scanner::scan_with_comments`), searched for directives.
+    pub fn with_comments(mut self, comments: Vec<Token>) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    fn report(&mut self, ty: LintTy, token: &Token, span: Span, related: Option<Token>) {
+        let level = self.levels.level(ty.lint());
+        if level == LintLevel::Allow { return; }
+        self.reports.push(LintReport { ty, token: token.clone(), span, level, related });
+    }
+
+    /// Reads the levels set by a directive comment, reporting unknown names.
+    fn directive(&mut self, comment: &Token) -> Vec<(&'static str, LintLevel)> {
+        let text = comment.lexeme.trim_start_matches('/').trim_start_matches('!').trim();
+        let Some(text) = text.strip_prefix("lint:") else { return Vec::new() };
+
+        let mut levels = Vec::new();
+        for group in text.split(')').map(str::trim).filter(|group| !group.is_empty()) {
+            let (level, ids) = group.split_once('(').unwrap_or((group, ""));
+            let Some(level) = LintLevel::parse(level.trim()) else {
+                self.report(LintTy::UnknownLint { name: level.trim().to_string() }, comment, comment.into(), None);
+                continue;
+            };
+            for id in ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+                match Lint::find(id) {
+                    Some(lint) => levels.push((lint.id, level)),
+                    None => self.report(LintTy::UnknownLint { name: id.to_string() }, comment, comment.into(), None),
+                }
+            }
+        }
+        levels
+    }
+
+    fn statement(&mut self, node: &BoxedASTNode, usages: &UsageCounter, declared: &mut HashMap<String, Token>) {
+        let n = node.borrow();
+        if let ASTNode::Test { ref body, .. } = **n {
+            let mut declared = declared.clone();
+            for node in body {
+                self.statement(node, usages, &mut declared);
+            }
+            return;
+        }
+        let ASTNode::VarDecl { ref name, ref initializer, constant, public, .. } = **n else {
+            return self.expression(node, Context::Statement);
+        };
+
+        let span = Span::from(name);
+        let exempt = !name.lexeme.starts_with(|c: char| c.is_alphabetic());
+        if !public && !exempt {
+            let id = node.meta.get::<BindingId>();
+            if id.is_some_and(|id| !usages.0.contains_key(&id)) {
+                self.report(LintTy::UnusedVariable, name, span, None);
+            }
+        }
+        if let Some(previous) = declared.insert(name.lexeme.clone(), name.clone()) {
+            self.report(LintTy::ShadowedBinding, name, span, Some(previous));
+        }
+        if let Some(expected) = Self::expected_name(&name.lexeme, constant).filter(|_| !exempt) {
+            self.report(LintTy::NamingConvention { expected }, name, span, None);
+        }
+        if let Some(initializer) = initializer {
+            self.expression(initializer, Context::Initializer);
+        }
+    }
+
+    /// Name following the convention, if `name` does not: variables are in camelCase,
+    /// constants in camelCase or UPPER_CASE.
+    fn expected_name(name: &str, constant: bool) -> Option<String> {
+        let uppercase = !name.chars().any(char::is_lowercase);
+        if uppercase && constant {
+            return None;
+        }
+
+        let mut expected = String::new();
+        for (i, word) in name.split('_').filter(|word| !word.is_empty()).enumerate() {
+            let word = if uppercase { word.to_lowercase() } else { word.to_string() };
+            let mut chars = word.chars();
+            let Some(first) = chars.next() else { continue };
+            if i == 0 {
+                expected.extend(first.to_lowercase());
+            } else {
+                expected.extend(first.to_uppercase());
+            }
+            expected.extend(chars);
+        }
+        (expected != name).then_some(expected)
+    }
+
+    fn expression(&mut self, node: &BoxedASTNode, context: Context) {
+        let n = node.borrow();
+        match **n {
+            ASTNode::Binary { ref left, ref operator, ref right } => {
+                if let Some(result) = Self::comparison_result(&operator.token_type) {
+                    if left.to_string() == right.to_string() && DeadCodeEliminator::is_pure(left) {
+                        self.report(LintTy::SelfComparison { result }, operator, n.span(), None);
+                    }
+                }
+                let precedence = Self::precedence(node);
+                self.expression(left, Context::Operand { precedence, left: true });
+                self.expression(right, Context::Operand { precedence, left: false });
+            },
+            ASTNode::Logical { ref left, ref operator, ref right } => {
+                if let Some(truthy) = Self::constant(left).map(|constant| constant.is_truthy()) {
+                    let short_circuits = truthy == (operator.token_type == TokenType::OR);
+                    self.report(LintTy::ConstantCondition { truthy, short_circuits }, operator, left.borrow().span(), None);
+                }
+                let precedence = Self::precedence(node);
+                self.expression(left, Context::Operand { precedence, left: true });
+                self.expression(right, Context::Operand { precedence, left: false });
+            },
+            ASTNode::Unary { ref right, .. } => self.expression(right, Context::Unary),
+            ASTNode::Grouping { ref expr } => {
+                let inner = Self::precedence(expr);
+                let redundant = match context {
+                    Context::Statement => inner == PRIMARY,
+                    Context::Initializer | Context::Grouping => true,
+                    Context::Unary => inner >= UNARY,
+                    Context::Operand { precedence, left } => inner > precedence || (inner == precedence && left),
+                };
+                if redundant {
+                    self.report(LintTy::RedundantParentheses, &expr.borrow().position(), expr.borrow().span(), None);
+                }
+                self.expression(expr, Context::Grouping);
+            },
+            ASTNode::Assert { ref condition, ref message, .. } => {
+                self.expression(condition, Context::Initializer);
+                if let Some(message) = message {
+                    self.expression(message, Context::Initializer);
+                }
+            },
+            ASTNode::Literal { .. } | ASTNode::Variable { .. } | ASTNode::VarDecl { .. } | ASTNode::Test { .. } => {},
+        }
+    }
+
+    /// Result of comparing a value to itself with the operator.
+    fn comparison_result(operator: &TokenType) -> Option<bool> {
+        match operator {
+            TokenType::EQUAL_EQUAL | TokenType::LESS_EQUAL | TokenType::GREATER_EQUAL => Some(true),
+            TokenType::BANG_EQUAL | TokenType::LESS | TokenType::GREATER => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Value of a literal, possibly in parentheses.
+    fn constant(node: &BoxedASTNode) -> Option<Constant> {
+        match **node.borrow() {
+            ASTNode::Literal { ref value } => Constant::from_token(value),
+            ASTNode::Grouping { ref expr } => Self::constant(expr),
+            _ => None,
+        }
+    }
+
+    /// How tightly an expression binds, higher binds tighter.
+    fn precedence(node: &BoxedASTNode) -> u8 {
+        use crate::scanner::TokenType::*;
+
+        match **node.borrow() {
+            ASTNode::Binary { ref operator, .. } | ASTNode::Logical { ref operator, .. } => match operator.token_type {
+                OR => 0,
+                AND => 1,
+                EQUAL_EQUAL | BANG_EQUAL => 2,
+                LESS | LESS_EQUAL | GREATER | GREATER_EQUAL => 3,
+                PLUS | MINUS => 4,
+                _ => 5,
+            },
+            ASTNode::Unary { .. } => UNARY,
+            _ => PRIMARY,
+        }
+    }
+}
+
+const UNARY: u8 = 6;
+const PRIMARY: u8 = 7;
+
+/// Where an expression appears, decides whether parentheses around it are needed.
+#[derive(Clone, Copy)]
+enum Context {
+    /// Expression statement. Parentheses may separate it from the previous statement, as in `a (-b)`.
+    Statement,
+    Initializer,
+    Grouping,
+    Unary,
+    Operand { precedence: u8, left: bool },
+}
+
+impl Pass for Linter {
+    /// Every report, if any lint is denied.
+    type Error = Vec<LintReport>;
+    /// Reported warnings.
+    type AdditionalData = Vec<LintReport>;
+
+    fn name(&self) -> &'static str {
+        "lint"
+    }
+
+    fn requires(&self) -> Vec<MetaKey> {
+        vec![MetaKey::of::<BindingId>()]
+    }
+
+    fn process(&mut self, ast: &mut Vec<BoxedASTNode>) -> Result<Self::AdditionalData, Self::Error> {
+        let comments = std::mem::take(&mut self.comments);
+        let mut statement_levels = vec![Vec::new(); ast.len()];
+        for comment in &comments {
+            let levels = self.directive(comment);
+            if comment.lexeme.starts_with("//!") {
+                for (id, level) in levels {
+                    self.config.set(id, level).unwrap();
+                }
+                self.levels = self.config.clone();
+            } else if let Some(i) = ast.iter().position(|node| node.borrow().span().end > comment.start) {
+                statement_levels[i].extend(levels);
+            }
+        }
+
+        // Declarations only read by unused ones are unused too.
+        let usages = UsageCounter::live(ast, |node| match **node.borrow() {
+            ASTNode::VarDecl { ref name, public: false, .. } => name.lexeme.starts_with(|c: char| c.is_alphabetic()),
+            _ => false,
+        });
+        let mut declared = HashMap::new();
+
+        for (node, levels) in ast.iter().zip(statement_levels) {
+            self.levels = self.config.clone();
+            for (id, level) in levels {
+                self.levels.set(id, level).unwrap();
+            }
+            self.statement(node, &usages, &mut declared);
+        }
+
+        let reports = std::mem::take(&mut self.reports);
+        if reports.iter().any(|report| report.level == LintLevel::Deny) {
+            Err(reports)
+        } else {
+            Ok(reports)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::passes::resolver::Resolver;
+
+    use super::*;
+
+    #[track_caller]
+    fn lint(code: &str, config: LintConfig) -> Result<Vec<String>, Vec<String>> {
+        let (tokens, comments) = crate::scanner::Scanner::new(code).scan_with_comments().unwrap();
+        let mut ast = crate::parser::Parser::new(tokens).parse().unwrap();
+        Resolver::new().process(&mut ast).unwrap();
+        let describe = |reports: Vec<LintReport>| reports.iter()
+            .map(|report| format!("{} {}", report.ty.lint().id, report.token.lexeme))
+            .collect();
+        Linter::new(config).with_comments(comments).process(&mut ast).map(describe).map_err(describe)
+    }
+
+    #[test]
+    fn test_lints() {
+        assert_eq!(
+            lint("let Total = (1); pub const LIMIT = 2; pub let x = Total == Total || (Total * 3) - 1; true && x", LintConfig::new()),
+            Ok(vec![
+                "naming-convention Total", "redundant-parentheses 1", "self-comparison ==",
+                "redundant-parentheses *", "constant-condition &&",
+            ].into_iter().map(String::from).collect())
+        );
+
+        let config = LintConfig::parse("[package]\nname = \"demo\"\n\n[lints]\nshadowed-binding = \"deny\" # strict\n").unwrap();
+        assert_eq!(
+            lint("//! lint: allow(unused-variable)\nlet a = 1; let b = 2;\n// lint: allow(shadowed-binding, typo)\nlet a = 3; let b = 4;", config),
+            Err(vec!["unknown-lint // lint: allow(shadowed-binding, typo)", "shadowed-binding b"]
+                .into_iter().map(String::from).collect())
+        );
+        assert!(LintConfig::parse("[lints]\nself-comparison = \"never\"").is_err());
+        assert_eq!(
+            lint("let a = 1; let b = a; let c = 2; let _d = c;", LintConfig::new()),
+            Ok(vec!["unused-variable a".to_string(), "unused-variable b".to_string()])
+        );
+        assert_eq!(
+            lint("pub let my_value = 1; pub const MAX_SIZE = 2; pub let MAX_COUNT = 3; pub let _private_value = 4;", LintConfig::new()),
+            Ok(vec!["naming-convention my_value".to_string(), "naming-convention MAX_COUNT".to_string()])
+        );
+
+        let analysis = crate::analyze("pub let max_size = 1;").unwrap();
+        let diagnostics: Vec<_> = analysis.warnings.iter().map(|warning| warning.diagnostic()).collect();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "'max_size' should be named 'maxSize'.");
+        assert_eq!(diagnostics[0].suggestions[0].replacement, "maxSize");
+    }
+
+    #[test]
+    fn test_expected_name() {
+        let expected = |name: &str, constant: bool| Linter::expected_name(name, constant);
+        assert_eq!(expected("camelCase", false), None);
+        assert_eq!(expected("snake_case", false).as_deref(), Some("snakeCase"));
+        assert_eq!(expected("my_Var", false).as_deref(), Some("myVar"));
+        assert_eq!(expected("Total", false).as_deref(), Some("total"));
+        assert_eq!(expected("MAX_SIZE", true), None);
+        assert_eq!(expected("MAX_SIZE", false).as_deref(), Some("maxSize"));
+        assert_eq!(expected("max_size", true).as_deref(), Some("maxSize"));
+    }
+}
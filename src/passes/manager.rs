@@ -0,0 +1,304 @@
+use std::{any::TypeId, io::Write, time::Instant};
+
+use colored::Colorize;
+
+use crate::{
+    parser::ast::{ASTNodeVecExt, BoxedASTNode},
+    util::{metacontainer::MetaContainer, trace::Tracer, HastyError},
+};
+
+use super::Pass;
+
+/// Identifies a kind of metadata that a pass can produce or require.
+#[derive(Debug, Clone, Copy)]
+pub struct MetaKey {
+    pub id: TypeId,
+    pub name: &'static str,
+}
+
+impl MetaKey {
+    pub fn of<T: 'static>() -> Self {
+        Self {
+            id: TypeId::of::<T>(),
+            name: std::any::type_name::<T>(),
+        }
+    }
+}
+
+impl PartialEq for MetaKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// Errors of a pass that can be collected into a list of diagnostics.
+pub trait PassErrors {
+    fn into_hasty_errors(self) -> Vec<Box<dyn HastyError>>;
+}
+
+impl<E: HastyError + 'static> PassErrors for Vec<E> {
+    fn into_hasty_errors(self) -> Vec<Box<dyn HastyError>> {
+        self.into_iter().map(|e| Box::new(e) as Box<dyn HastyError>).collect()
+    }
+}
+
+/// Errors caused by invalid pass configuration.
+#[derive(Debug)]
+pub enum PassManagerError {
+    /// Pass requires metadata that no registered pass produces.
+    MissingRequirement {
+        pass: &'static str,
+        meta: &'static str,
+    },
+    /// Passes depend on each other, so there is no valid order.
+    DependencyCycle {
+        passes: Vec<&'static str>,
+    },
+}
+
+impl HastyError for PassManagerError {
+    fn as_hasty_error_string(&self, _source: &str) -> String {
+        format!("[{}] {} {}\n", "PASSES".yellow(), "Error:".red(), self.get_error_description().red())
+    }
+
+    fn get_error_description(&self) -> String {
+        match self {
+            PassManagerError::MissingRequirement { pass, meta } =>
+                format!("Pass '{}' requires '{}', but no pass produces it.", pass, meta),
+            PassManagerError::DependencyCycle { passes } =>
+                format!("Dependency cycle between passes: {}.", passes.join(", ")),
+        }
+    }
+}
+
+/// Object safe version of `Pass`, used internally by `PassManager`.
+trait AnyPass {
+    fn name(&self) -> &'static str;
+    fn produces(&self) -> Vec<MetaKey>;
+    fn requires(&self) -> Vec<MetaKey>;
+    fn run(&mut self, ast: &mut Vec<BoxedASTNode>, results: &MetaContainer) -> Vec<Box<dyn HastyError>>;
+}
+
+impl<P> AnyPass for P
+where
+    P: Pass,
+    P::Error: PassErrors,
+    P::AdditionalData: 'static,
+{
+    fn name(&self) -> &'static str {
+        Pass::name(self)
+    }
+
+    fn produces(&self) -> Vec<MetaKey> {
+        Pass::produces(self)
+    }
+
+    fn requires(&self) -> Vec<MetaKey> {
+        Pass::requires(self)
+    }
+
+    fn run(&mut self, ast: &mut Vec<BoxedASTNode>, results: &MetaContainer) -> Vec<Box<dyn HastyError>> {
+        match self.process(ast) {
+            Ok(data) => {
+                results.insert(data);
+                Vec::new()
+            },
+            Err(errors) => errors.into_hasty_errors(),
+        }
+    }
+}
+
+/// Runs registered passes in an order that satisfies their requirements.
+pub struct PassManager {
+    passes: Vec<Box<dyn AnyPass>>,
+    /// Additional data returned by passes, accessible by its type.
+    results: MetaContainer,
+    /// Names of passes after which AST should be dumped.
+    dump_after: Vec<String>,
+    dump_target: Box<dyn Write>,
+    tracer: Option<Tracer>,
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            results: MetaContainer::new(),
+            dump_after: Vec::new(),
+            dump_target: Box::new(std::io::stderr()),
+            tracer: None,
+        }
+    }
+
+    /// Registers new pass.
+    pub fn register<P>(&mut self, pass: P) -> &mut Self
+    where
+        P: Pass + 'static,
+        P::Error: PassErrors,
+        P::AdditionalData: 'static,
+    {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Dump AST after pass with given name has finished.
+    pub fn dump_after(&mut self, pass: &str) -> &mut Self {
+        self.dump_after.push(pass.to_string());
+        self
+    }
+
+    /// Sets where AST dumps are written (stderr by default).
+    pub fn dump_to(&mut self, target: impl Write + 'static) -> &mut Self {
+        self.dump_target = Box::new(target);
+        self
+    }
+
+    /// Enables tracing of how long each pass takes.
+    pub fn trace(&mut self, tracer: Tracer) -> &mut Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Get additional data returned by one of the passes.
+    pub fn result<T: 'static>(&self) -> Option<std::rc::Rc<T>> {
+        self.results.get::<T>()
+    }
+
+    /// Names of registered passes in the order they would run.
+    pub fn order(&self) -> Result<Vec<&'static str>, PassManagerError> {
+        Ok(self.schedule()?.into_iter().map(|i| self.passes[i].name()).collect())
+    }
+
+    /// Computes order of passes. Registration order is kept whenever possible.
+    fn schedule(&self) -> Result<Vec<usize>, PassManagerError> {
+        // Every requirement has to be produced by some pass.
+        for pass in self.passes.iter() {
+            for required in pass.requires() {
+                if !self.passes.iter().any(|p| p.produces().contains(&required)) {
+                    return Err(PassManagerError::MissingRequirement {
+                        pass: pass.name(),
+                        meta: required.name,
+                    });
+                }
+            }
+        }
+
+        let mut order = Vec::new();
+        let mut produced = Vec::new();
+        while order.len() < self.passes.len() {
+            let next = (0..self.passes.len())
+                .filter(|i| !order.contains(i))
+                .find(|&i| self.passes[i].requires().iter().all(|r| produced.contains(r)));
+
+            let Some(next) = next else {
+                return Err(PassManagerError::DependencyCycle {
+                    passes: (0..self.passes.len())
+                        .filter(|i| !order.contains(i))
+                        .map(|i| self.passes[i].name())
+                        .collect(),
+                });
+            };
+
+            produced.extend(self.passes[next].produces());
+            order.push(next);
+        }
+
+        Ok(order)
+    }
+
+    /// Runs all passes. Errors from every pass are collected, but passes
+    /// whose requirements were produced by a failed pass are skipped.
+    pub fn run(&mut self, ast: &mut Vec<BoxedASTNode>) -> Result<(), Vec<Box<dyn HastyError>>> {
+        let order = self.schedule().map_err(|e| vec![Box::new(e) as Box<dyn HastyError>])?;
+
+        let mut errors = Vec::new();
+        let mut failed: Vec<MetaKey> = Vec::new();
+        for i in order {
+            let pass = &mut self.passes[i];
+            if pass.requires().iter().any(|r| failed.contains(r)) {
+                failed.extend(pass.produces());
+                continue;
+            }
+
+            let started = Instant::now();
+            let pass_errors = pass.run(ast, &self.results);
+            if let Some(tracer) = &self.tracer {
+                tracer.pass(pass.name(), started.elapsed());
+            }
+            if !pass_errors.is_empty() {
+                failed.extend(pass.produces());
+                errors.extend(pass_errors);
+            }
+
+            if self.dump_after.iter().any(|name| name == pass.name()) {
+                let _ = write!(self.dump_target, "// AST after '{}'\n{}", pass.name(), ast.display(0));
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{passes::resolver::{BindingId, Bindings, Resolver}, util::testing::{parse, SharedBuffer}};
+
+    use super::*;
+
+    /// Pass that only checks that bindings were already resolved.
+    struct NeedsBindings;
+
+    impl Pass for NeedsBindings {
+        type Error = Vec<PassManagerError>;
+        type AdditionalData = ();
+
+        fn name(&self) -> &'static str { "needs-bindings" }
+        fn requires(&self) -> Vec<MetaKey> { vec![MetaKey::of::<BindingId>()] }
+
+        fn process(&mut self, ast: &mut Vec<BoxedASTNode>) -> Result<(), Self::Error> {
+            assert!(ast[1].meta.get::<BindingId>().is_some());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_pass_order_and_dump() {
+        let dump = SharedBuffer::default();
+        let mut manager = PassManager::new();
+        manager
+            .register(NeedsBindings)
+            .register(Resolver::new())
+            .dump_after("resolver")
+            .dump_to(dump.clone());
+
+        assert_eq!(manager.order().unwrap(), vec!["resolver", "needs-bindings"]);
+
+        let mut ast = parse("let x = 1; x");
+        assert!(manager.run(&mut ast).is_ok());
+        assert_eq!(manager.result::<Bindings>().unwrap().0.len(), 1);
+        assert_eq!(
+            dump.contents(),
+            "// AST after 'resolver'\n{\n    (letvardecl x = 1)\n    (var x)\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_errors_skip_dependent_passes() {
+        let mut manager = PassManager::new();
+        manager.register(Resolver::new()).register(NeedsBindings);
+
+        let mut ast = parse("y x");
+        let Err(errors) = manager.run(&mut ast) else { panic!() };
+        assert_eq!(errors.len(), 2);
+
+        let mut manager = PassManager::new();
+        manager.register(NeedsBindings);
+        assert!(matches!(manager.order(), Err(PassManagerError::MissingRequirement { .. })));
+    }
+}
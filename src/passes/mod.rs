@@ -1,8 +1,33 @@
 use crate::parser::ast::BoxedASTNode;
 
+use self::manager::MetaKey;
+
+pub mod constant_folding;
+pub mod dead_code;
+pub mod lint;
+pub mod manager;
+pub mod resolver;
+pub mod typecheck;
+
 /// Layer for processing ASTNodeMeta
 pub trait Pass {
     type Error;
     type AdditionalData;
     fn process(&mut self, ast: &mut Vec<BoxedASTNode>) -> Result<Self::AdditionalData, Self::Error>;
-}
\ No newline at end of file
+
+    /// Name of the pass (used for debugging and AST dumps).
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    /// Metadata this pass attaches to AST nodes.
+    fn produces(&self) -> Vec<MetaKey> {
+        Vec::new()
+    }
+
+    /// Metadata that has to be attached before this pass runs.
+    fn requires(&self) -> Vec<MetaKey> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use crate::{
+    parser::{ast::{ASTNode, BoxedASTNode}, visitor::{walk_ast, walk_test, walk_var_decl, Visitor}},
+    scanner::Token,
+    util::{diagnostic::Diagnostic, scope::Scope, unified_error, HastyError},
+};
+
+use super::{manager::MetaKey, Pass};
+
+/// Unique identifier of a single declaration.
+/// Attached to both `VarDecl` and `Variable` nodes, so that every
+/// variable reference points to exactly one declaration (even when shadowed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BindingId(pub usize);
+
+/// Information about a declaration found by the resolver.
+#[derive(Debug, Clone)]
+pub struct Binding {
+    /// Id of this binding.
+    pub id: BindingId,
+    /// Name token of the declaration.
+    pub name: Token,
+    /// Declaration node.
+    pub declaration: BoxedASTNode,
+}
+
+/// All bindings found in a program, indexed by `BindingId`.
+#[derive(Debug, Clone, Default)]
+pub struct Bindings(pub Vec<Binding>);
+
+impl Bindings {
+    pub fn get(&self, id: BindingId) -> Option<&Binding> {
+        self.0.get(id.0)
+    }
+}
+
+#[derive(Debug)]
+pub enum ResolverErrorTy {
+    /// Variable is declared in this scope, but only after it is used.
+    UseBeforeDeclaration,
+    /// Variable is not declared anywhere.
+    UndefinedVariable,
+}
+
+#[derive(Debug)]
+pub struct ResolverError {
+    pub ty: ResolverErrorTy,
+    pub token: Token,
+    /// Declaration that comes later, or declaration with a similar name.
+    pub related: Option<Token>,
+}
+
+impl ResolverError {
+    pub fn new(ty: ResolverErrorTy, token: Token) -> Self {
+        Self {
+            ty,
+            token,
+            related: None,
+        }
+    }
+}
+
+impl HastyError for ResolverError {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("RESOLVER", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match &self.ty {
+            ResolverErrorTy::UseBeforeDeclaration => format!("Variable '{}' used before its declaration.", self.token.lexeme),
+            ResolverErrorTy::UndefinedVariable => format!("Undefined variable '{}'.", self.token.lexeme),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+
+    fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(self.get_error_description());
+        match (&self.ty, &self.related) {
+            (ResolverErrorTy::UseBeforeDeclaration, related) => {
+                let diagnostic = diagnostic.with_code("E0201")
+                    .with_primary(&self.token, "used here")
+                    .with_help("move the declaration before its first use");
+                match related {
+                    Some(declaration) => diagnostic.with_secondary(declaration, "declared here"),
+                    None => diagnostic,
+                }
+            },
+            (ResolverErrorTy::UndefinedVariable, Some(similar)) => diagnostic.with_code("E0202")
+                .with_primary(&self.token, "not found in this scope")
+                .with_secondary(similar, "similar variable declared here")
+                .with_suggestion(format!("did you mean '{}'?", similar.lexeme), &self.token, similar.lexeme.as_str()),
+            (ResolverErrorTy::UndefinedVariable, None) => diagnostic.with_code("E0202")
+                .with_primary(&self.token, "not found in this scope"),
+        }
+    }
+}
+
+/// Pass that resolves every variable reference to its declaration.
+/// Each declaration gets a unique `BindingId` so that later passes
+/// do not have to care about shadowing.
+pub struct Resolver {
+    scope: Scope<BindingId>,
+    /// Names declared anywhere in the current scope with their first declaration
+    /// (used for better error messages).
+    declared_later: HashMap<String, Token>,
+    /// Tests, resolved after the module, since they run after it.
+    tests: Vec<BoxedASTNode>,
+    bindings: Bindings,
+    errors: Vec<ResolverError>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scope: Scope::new(),
+            declared_later: HashMap::new(),
+            tests: Vec::new(),
+            bindings: Bindings::default(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Creates new binding and inserts it into current scope.
+    fn declare(&mut self, name: &Token, declaration: &BoxedASTNode) -> BindingId {
+        let id = BindingId(self.bindings.0.len());
+        self.bindings.0.push(Binding {
+            id,
+            name: name.clone(),
+            declaration: declaration.clone(),
+        });
+        self.scope.insert(&name.lexeme, id);
+        id
+    }
+
+    /// Declaration in scope with a name that is most likely a typo of `name`.
+    fn similar(&self, name: &str) -> Option<Token> {
+        let length = name.chars().count();
+        self.scope.names().into_iter()
+            .map(|candidate| (edit_distance(name, &candidate), candidate))
+            .filter(|(distance, _)| *distance <= length.div_ceil(3) && *distance < length)
+            .min_by_key(|(distance, _)| *distance)
+            .and_then(|(_, candidate)| self.scope.get(&candidate))
+            .map(|id| self.bindings.0[id.0].name.clone())
+    }
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl Visitor for Resolver {
+    fn visit_var_decl(&mut self, node: &BoxedASTNode, name: &Token, _ty: Option<&Token>, initializer: Option<&BoxedASTNode>, _constant: bool, _public: bool) {
+        // Initializer is resolved before the declaration,
+        // so `let x = x + 1;` refers to previous `x`.
+        walk_var_decl(self, initializer);
+        let id = self.declare(name, node);
+        node.meta.insert(id);
+    }
+
+    fn visit_test(&mut self, node: &BoxedASTNode, _name: &Token, _body: &[BoxedASTNode]) {
+        self.tests.push(node.clone());
+    }
+
+    fn visit_variable(&mut self, node: &BoxedASTNode, name: &Token) {
+        if let Some(id) = self.scope.get(&name.lexeme) {
+            node.meta.insert(id);
+        } else if let Some(declaration) = self.declared_later.get(&name.lexeme) {
+            let mut error = ResolverError::new(ResolverErrorTy::UseBeforeDeclaration, name.clone());
+            error.related = Some(declaration.clone());
+            self.errors.push(error);
+        } else {
+            let mut error = ResolverError::new(ResolverErrorTy::UndefinedVariable, name.clone());
+            error.related = self.similar(&name.lexeme);
+            self.errors.push(error);
+        }
+    }
+}
+
+impl Pass for Resolver {
+    type Error = Vec<ResolverError>;
+    type AdditionalData = Bindings;
+
+    fn name(&self) -> &'static str {
+        "resolver"
+    }
+
+    fn produces(&self) -> Vec<MetaKey> {
+        vec![MetaKey::of::<BindingId>()]
+    }
+
+    fn process(&mut self, ast: &mut Vec<BoxedASTNode>) -> Result<Self::AdditionalData, Self::Error> {
+        for node in ast.iter() {
+            if let ASTNode::VarDecl { ref name, .. } = **node.borrow() {
+                self.declared_later.entry(name.lexeme.clone()).or_insert_with(|| name.clone());
+            }
+        }
+
+        walk_ast(self, ast);
+
+        // Every test has its own scope, which sees all declarations of the module.
+        let module = self.scope.clone();
+        for test in std::mem::take(&mut self.tests) {
+            self.scope = module.child();
+            if let ASTNode::Test { ref body, .. } = **test.borrow() {
+                walk_test(self, body);
+            }
+        }
+        self.scope = module;
+
+        if self.errors.is_empty() {
+            Ok(std::mem::take(&mut self.bindings))
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::testing::parse;
+
+    use super::*;
+
+    #[track_caller]
+    fn resolve(code: &str) -> (Vec<BoxedASTNode>, Result<Bindings, Vec<ResolverError>>) {
+        let mut ast = parse(code);
+        let result = Resolver::new().process(&mut ast);
+        (ast, result)
+    }
+
+    #[test]
+    fn test_resolve_shadowing() {
+        let (ast, bindings) = resolve("let x = 1; let x = x + 1; x");
+        assert_eq!(bindings.unwrap().0.len(), 2);
+
+        // `x` in the second initializer refers to the first declaration.
+        let ASTNode::VarDecl { initializer: Some(ref init), .. } = **ast[1].borrow() else { panic!() };
+        let ASTNode::Binary { ref left, .. } = **init.borrow() else { panic!() };
+        assert_eq!(*left.meta.get::<BindingId>().unwrap(), BindingId(0));
+
+        // Last `x` refers to the second one.
+        assert_eq!(*ast[2].meta.get::<BindingId>().unwrap(), BindingId(1));
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let (_, result) = resolve("y let x = x; z");
+        let errors = result.unwrap_err();
+        assert!(matches!(errors[0].ty, ResolverErrorTy::UndefinedVariable));
+        assert!(matches!(errors[1].ty, ResolverErrorTy::UseBeforeDeclaration));
+        assert!(matches!(errors[2].ty, ResolverErrorTy::UndefinedVariable));
+
+        let (_, result) = resolve("let count = 1; cuont");
+        let errors = result.unwrap_err();
+        assert_eq!(errors[0].related.as_ref().unwrap().lexeme, "count");
+        assert_eq!(errors[0].diagnostic().suggestions[0].replacement, "count");
+    }
+}
@@ -0,0 +1,340 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    parser::ast::{ASTNode, BoxedASTNode},
+    scanner::{Token, TokenType},
+    util::{diagnostic::Diagnostic, scope::Scope, unified_error, HastyError},
+};
+
+use super::{manager::MetaKey, resolver::BindingId, Pass};
+
+/// Type of a value, attached to every expression and declaration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Float,
+    String,
+    Bool,
+    Nil,
+}
+
+impl Type {
+    /// Reads type from an annotation like `int`.
+    pub fn from_annotation(token: &Token) -> Option<Self> {
+        Some(match token.lexeme.as_str() {
+            "int" => Type::Int,
+            "float" => Type::Float,
+            "string" => Type::String,
+            "bool" => Type::Bool,
+            "nil" => Type::Nil,
+            _ => return None,
+        })
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Type::Int | Type::Float)
+    }
+
+    /// Whether value of type `other` can be stored in a binding of this type.
+    pub fn accepts(&self, other: Type) -> bool {
+        *self == other || (*self == Type::Float && other == Type::Int)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::String => "string",
+            Type::Bool => "bool",
+            Type::Nil => "nil",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum TypeErrorTy {
+    /// Type annotation does not name any known type.
+    UnknownType,
+    /// Operator can not be applied to operands of these types.
+    InvalidOperands {
+        left: Option<Type>,
+        right: Type,
+    },
+    /// Initializer does not match declared type.
+    Mismatch {
+        expected: Type,
+        found: Type,
+    },
+}
+
+#[derive(Debug)]
+pub struct TypeError {
+    pub ty: TypeErrorTy,
+    pub token: Token,
+}
+
+impl HastyError for TypeError {
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("TYPE CHECKER", &self.get_error_description(), source, &self.token)
+    }
+
+    fn get_error_description(&self) -> String {
+        match &self.ty {
+            TypeErrorTy::UnknownType => format!("Unknown type '{}'.", self.token.lexeme),
+            TypeErrorTy::InvalidOperands { left: Some(left), right } =>
+                format!("Operator '{}' can not be applied to {} and {}.", self.token.lexeme, left, right),
+            TypeErrorTy::InvalidOperands { left: None, right } =>
+                format!("Operator '{}' can not be applied to {}.", self.token.lexeme, right),
+            TypeErrorTy::Mismatch { expected, found } => format!("Expected {}, found {}.", expected, found),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+    fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(self.get_error_description());
+        match &self.ty {
+            TypeErrorTy::UnknownType => {
+                let diagnostic = diagnostic.with_code("E0301")
+                    .with_primary(&self.token, "unknown type")
+                    .with_note("known types are int, float, string, bool and nil");
+                // Names of types in other languages.
+                let known = match self.token.lexeme.as_str() {
+                    "number" | "double" => Some("float"),
+                    "integer" | "i64" => Some("int"),
+                    "boolean" => Some("bool"),
+                    "str" | "String" => Some("string"),
+                    "null" | "void" => Some("nil"),
+                    _ => None,
+                };
+                match known {
+                    Some(known) => diagnostic.with_suggestion(format!("use '{}' instead", known), &self.token, known),
+                    None => diagnostic,
+                }
+            },
+            TypeErrorTy::InvalidOperands { left: Some(left), right } => diagnostic.with_code("E0302")
+                .with_primary(&self.token, format!("can not be applied to {} and {}", left, right)),
+            TypeErrorTy::InvalidOperands { left: None, right } => diagnostic.with_code("E0302")
+                .with_primary(&self.token, format!("can not be applied to {}", right)),
+            TypeErrorTy::Mismatch { expected, found } => {
+                let diagnostic = diagnostic.with_code("E0303")
+                    .with_primary(&self.token, format!("expected {}, found {}", expected, found));
+                if *expected == Type::Int && *found == Type::Float {
+                    diagnostic.with_help("declare it as float, integers can be stored in floats but not the other way")
+                } else {
+                    diagnostic
+                }
+            },
+        }
+    }
+}
+
+/// Pass that infers type of every expression and checks declared types.
+/// Types are attached to nodes as `Type` metadata.
+pub struct TypeChecker {
+    bindings: HashMap<BindingId, Type>,
+    /// Types of variables without a binding, looked up by name.
+    globals: Option<Scope<Type>>,
+    errors: Vec<TypeError>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        Self {
+            bindings: HashMap::new(),
+            globals: None,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Type checker for programs checked piece by piece, like REPL entries.
+    /// Declarations are also stored in `globals` by name, so they are visible in later programs,
+    /// where variables without a binding are looked up by name.
+    pub fn with_globals(globals: Scope<Type>) -> Self {
+        Self {
+            globals: Some(globals),
+            ..Self::new()
+        }
+    }
+
+    /// Infers type of a node. After an error, best guess is returned to avoid cascading errors.
+    fn check(&mut self, node: &BoxedASTNode) -> Type {
+        let n = node.borrow();
+        let ty = match **n {
+            ASTNode::Binary { ref left, ref operator, ref right } => {
+                let (l, r) = (self.check(left), self.check(right));
+                self.check_binary(operator, l, r)
+            },
+            ASTNode::Logical { ref left, ref operator, ref right } => {
+                let (l, r) = (self.check(left), self.check(right));
+                if l != r {
+                    self.error(TypeErrorTy::InvalidOperands { left: Some(l), right: r }, operator);
+                }
+                l
+            },
+            ASTNode::Unary { ref operator, ref right } => {
+                let r = self.check(right);
+                match operator.token_type {
+                    TokenType::BANG => Type::Bool,
+                    _ if r.is_numeric() => r,
+                    _ => {
+                        self.error(TypeErrorTy::InvalidOperands { left: None, right: r }, operator);
+                        r
+                    },
+                }
+            },
+            ASTNode::Literal { ref value } => match value.token_type {
+                TokenType::INTEGER => Type::Int,
+                TokenType::FLOATING => Type::Float,
+                TokenType::STRING => Type::String,
+                TokenType::TRUE | TokenType::FALSE => Type::Bool,
+                _ => Type::Nil,
+            },
+            ASTNode::Grouping { ref expr } => self.check(expr),
+            ASTNode::VarDecl { ref name, ref ty, ref initializer, .. } => {
+                let declared = ty.as_ref().and_then(|ty| {
+                    let declared = Type::from_annotation(ty);
+                    if declared.is_none() { self.error(TypeErrorTy::UnknownType, ty); }
+                    declared
+                });
+                let found = initializer.as_ref().map(|initializer| self.check(initializer));
+
+                let ty = match (declared, found) {
+                    (Some(expected), Some(found)) => {
+                        if !expected.accepts(found) {
+                            let token = initializer.as_ref().unwrap().borrow().position();
+                            self.error(TypeErrorTy::Mismatch { expected, found }, &token);
+                        }
+                        expected
+                    },
+                    (Some(ty), None) | (None, Some(ty)) => ty,
+                    (None, None) => Type::Nil,
+                };
+
+                if let Some(id) = node.meta.get::<BindingId>() {
+                    self.bindings.insert(*id, ty);
+                }
+                if let Some(globals) = &self.globals {
+                    globals.insert(&name.lexeme, ty);
+                }
+                ty
+            },
+            ASTNode::Variable { ref name } => match node.meta.get::<BindingId>() {
+                Some(id) => self.bindings.get(&id).copied(),
+                None => self.globals.as_ref().and_then(|globals| globals.get(&name.lexeme)),
+            }.unwrap_or(Type::Nil),
+            ASTNode::Test { ref body, .. } => {
+                for node in body {
+                    self.check(node);
+                }
+                Type::Nil
+            },
+            // Any value can be asserted, it only has to be truthy.
+            ASTNode::Assert { ref condition, ref message, .. } => {
+                self.check(condition);
+                if let Some(message) = message { self.check(message); }
+                Type::Nil
+            },
+        };
+
+        node.meta.insert(ty);
+        ty
+    }
+
+    fn check_binary(&mut self, operator: &Token, left: Type, right: Type) -> Type {
+        let result = match operator.token_type {
+            TokenType::EQUAL_EQUAL | TokenType::BANG_EQUAL => Some(Type::Bool),
+            TokenType::PLUS if left == Type::String && right == Type::String => Some(Type::String),
+            // Division has the same result as in javascript, so it is never an integer.
+            TokenType::SLASH if left.is_numeric() && right.is_numeric() => Some(Type::Float),
+            TokenType::PLUS | TokenType::MINUS | TokenType::STAR if left.is_numeric() && right.is_numeric() => {
+                Some(if left == Type::Int && right == Type::Int { Type::Int } else { Type::Float })
+            },
+            TokenType::LESS | TokenType::LESS_EQUAL | TokenType::GREATER | TokenType::GREATER_EQUAL
+                if (left.is_numeric() && right.is_numeric()) || (left == Type::String && right == Type::String) => {
+                Some(Type::Bool)
+            },
+            _ => None,
+        };
+
+        result.unwrap_or_else(|| {
+            self.error(TypeErrorTy::InvalidOperands { left: Some(left), right }, operator);
+            left
+        })
+    }
+
+    fn error(&mut self, ty: TypeErrorTy, token: &Token) {
+        self.errors.push(TypeError { ty, token: token.clone() });
+    }
+}
+
+impl Pass for TypeChecker {
+    type Error = Vec<TypeError>;
+    type AdditionalData = ();
+
+    fn name(&self) -> &'static str {
+        "type-checker"
+    }
+
+    fn requires(&self) -> Vec<MetaKey> {
+        vec![MetaKey::of::<BindingId>()]
+    }
+
+    fn produces(&self) -> Vec<MetaKey> {
+        vec![MetaKey::of::<Type>()]
+    }
+
+    fn process(&mut self, ast: &mut Vec<BoxedASTNode>) -> Result<Self::AdditionalData, Self::Error> {
+        // Tests run after the whole module, so they are checked once every declaration has a type.
+        let (tests, statements): (Vec<_>, Vec<_>) = ast.iter()
+            .partition(|node| matches!(**node.borrow(), ASTNode::Test { .. }));
+        for node in statements.into_iter().chain(tests) {
+            self.check(node);
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::testing::resolve;
+
+    use super::*;
+
+    #[track_caller]
+    fn check(code: &str) -> Result<Vec<Type>, Vec<TypeError>> {
+        let mut ast = resolve(code);
+        TypeChecker::new().process(&mut ast)?;
+        Ok(ast.iter().map(|node| *node.meta.get::<Type>().unwrap()).collect())
+    }
+
+    #[test]
+    fn test_infer_types() {
+        assert_eq!(
+            check("let x: float = 1; x * 2 1 / 2 \"a\" + \"b\" 1 < 2 || false (-3)").unwrap(),
+            vec![Type::Float, Type::Float, Type::Float, Type::String, Type::Bool, Type::Int]
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let errors = check("let x: int = 1.5; let y: number = 1; \"a\" - 1").unwrap_err();
+        assert!(matches!(errors[0].ty, TypeErrorTy::Mismatch { expected: Type::Int, found: Type::Float }));
+        assert!(matches!(errors[1].ty, TypeErrorTy::UnknownType));
+        assert!(matches!(errors[2].ty, TypeErrorTy::InvalidOperands { left: Some(Type::String), right: Type::Int }));
+    }
+}
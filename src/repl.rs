@@ -0,0 +1,238 @@
+use crate::{
+    backend::interpreter::{Interpreter, Value},
+    compiler::Compiler,
+    parser::{ast::{ASTNode, ASTNodeVecExt, BoxedASTNode}, Parser},
+    passes::{resolver::Resolver, typecheck::{Type, TypeChecker}, Pass},
+    scanner::{Scanner, ScannerErrorTy, TokenType},
+    util::{scope::Scope, HastyError},
+};
+
+const HELP: &str = "\
+Enter code to evaluate it. Unfinished input continues on the next line,
+an empty line submits it anyway.
+
+Commands:
+    :tokens <code>    Show tokens
+    :ast <code>       Show AST
+    :type <code>      Show type without evaluating
+    :js <code>        Show compiled javascript
+    :help             Show this message
+    :quit             Exit";
+
+/// Result of feeding a line into the REPL.
+#[derive(Debug, PartialEq)]
+pub enum Feed {
+    /// Input is not finished yet, more lines are needed.
+    Incomplete,
+    /// Text to show, possibly empty.
+    Output(String),
+    Error(String),
+    Exit,
+}
+
+/// Read-eval-print loop. Values and types of variables are kept between entries in persistent scopes.
+/// Every entry runs in child scopes, which are only committed if it was evaluated successfully.
+pub struct Repl {
+    buffer: String,
+    values: Scope<Value>,
+    types: Scope<Type>,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+            values: Scope::new(),
+            types: Scope::new(),
+        }
+    }
+
+    /// Prompt to show before reading the next line.
+    pub fn prompt(&self) -> &'static str {
+        if self.buffer.is_empty() { "> " } else { "... " }
+    }
+
+    pub fn feed(&mut self, line: &str) -> Feed {
+        let submit = line.trim().is_empty();
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+
+        let input = self.buffer.trim().to_string();
+        let (command, code) = match input.strip_prefix(':') {
+            Some(command) => command.split_once(char::is_whitespace).unwrap_or((command, "")),
+            None => ("", input.as_str()),
+        };
+
+        if !submit && Self::is_incomplete(code) {
+            return Feed::Incomplete;
+        }
+        self.buffer.clear();
+
+        match command {
+            "" if code.is_empty() => Feed::Output(String::new()),
+            "" => self.evaluate(code),
+            "tokens" => match Scanner::new(code).scan() {
+                Ok(tokens) => Feed::Output(tokens.iter().map(|token| token.to_string()).collect::<Vec<_>>().join("\n")),
+                Err(error) => Feed::Error(error.diagnostic().render(code)),
+            },
+            "ast" => match Self::parse(code) {
+                Ok(ast) => Feed::Output(ast.display(0).trim_end().to_string()),
+                Err(error) => Feed::Error(error),
+            },
+            "type" => match self.check(code) {
+                Ok((ast, _)) => Feed::Output(ast.last().and_then(|node| node.meta.get::<Type>())
+                    .map(|ty| ty.to_string())
+                    .unwrap_or_default()),
+                Err(error) => Feed::Error(error),
+            },
+            "js" => self.javascript(code),
+            "help" => Feed::Output(HELP.to_string()),
+            "quit" | "q" => Feed::Exit,
+            _ => Feed::Error(format!("Unknown command ':{}', see :help.", command)),
+        }
+    }
+
+    fn evaluate(&mut self, code: &str) -> Feed {
+        let (ast, types) = match self.check(code) {
+            Ok(checked) => checked,
+            Err(error) => return Feed::Error(error),
+        };
+
+        let values = self.values.child();
+        let result = Interpreter::with_globals(values.clone()).interpret(&ast);
+        if result.is_ok() {
+            values.commit();
+            types.commit();
+        }
+        match result {
+            Ok(_) if ast.last().is_some_and(|node| matches!(**node.borrow(), ASTNode::VarDecl { .. })) => {
+                Feed::Output(String::new())
+            },
+            Ok(value) => Feed::Output(value.to_string()),
+            Err(error) => Feed::Error(error.diagnostic().render(code)),
+        }
+    }
+
+    fn javascript(&mut self, code: &str) -> Feed {
+        // Shadowed declarations are renamed by the compiler, so bindings have to be resolved.
+        let ast = match Self::parse(code).and_then(|mut ast| self.resolve(&mut ast, code).map(|_| ast)) {
+            Ok(ast) => ast,
+            Err(error) => return Feed::Error(error),
+        };
+
+        let mut result = Vec::new();
+        match Compiler::new().compile_ast(&ast, &mut result) {
+            Ok(_) => Feed::Output(String::from_utf8_lossy(&result).trim_end().to_string()),
+            Err(error) => Feed::Error(error.diagnostic().render(code)),
+        }
+    }
+
+    /// Parses, resolves and type checks entry, using types of variables from previous entries.
+    /// Types of new variables are returned in a child scope, to be committed by the caller.
+    fn check(&self, code: &str) -> Result<(Vec<BoxedASTNode>, Scope<Type>), String> {
+        let mut ast = Self::parse(code)?;
+        self.resolve(&mut ast, code)?;
+        let types = self.types.child();
+        TypeChecker::with_globals(types.clone())
+            .process(&mut ast)
+            .map_err(|errors| Self::render(&errors, code))?;
+        Ok((ast, types))
+    }
+
+    /// Resolves bindings of entry. Variables of previous entries are unknown to the resolver,
+    /// so using them is not an error.
+    fn resolve(&self, ast: &mut Vec<BoxedASTNode>, code: &str) -> Result<(), String> {
+        let Err(errors) = Resolver::new().process(ast) else { return Ok(()) };
+        let errors: Vec<_> = errors.into_iter()
+            .filter(|error| self.types.get(&error.token.lexeme).is_none())
+            .collect();
+        if errors.is_empty() { Ok(()) } else { Err(Self::render(&errors, code)) }
+    }
+
+    fn parse(code: &str) -> Result<Vec<BoxedASTNode>, String> {
+        let tokens = Scanner::new(code).scan().map_err(|error| error.diagnostic().render(code))?;
+        Parser::new(tokens).parse().map_err(|error| error.diagnostic().render(code))
+    }
+
+    /// Whether input ends inside a string, parentheses or braces,
+    /// or the parser ran out of tokens.
+    fn is_incomplete(code: &str) -> bool {
+        let tokens = match Scanner::new(code).scan() {
+            Ok(tokens) => tokens,
+            Err(error) => return matches!(error.ty, ScannerErrorTy::UnterminatedString),
+        };
+
+        let mut depth = 0isize;
+        for token in &tokens {
+            match token.token_type {
+                TokenType::LEFT_PAREN | TokenType::LEFT_BRACE => depth += 1,
+                TokenType::RIGHT_PAREN | TokenType::RIGHT_BRACE => depth -= 1,
+                _ => {},
+            }
+        }
+        if depth > 0 { return true; }
+
+        match Parser::new(tokens).parse() {
+            Err(error) => error.token().is_some_and(|token| token.token_type == TokenType::EOF),
+            Ok(_) => false,
+        }
+    }
+
+    fn render(errors: &[impl HastyError], code: &str) -> String {
+        errors.iter().map(|error| error.diagnostic().render(code)).collect::<Vec<_>>().join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repl() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.feed("let x = 2;"), Feed::Output(String::new()));
+        assert_eq!(repl.feed("x * (3 +"), Feed::Incomplete);
+        assert_eq!(repl.prompt(), "... ");
+        assert_eq!(repl.feed("1)"), Feed::Output("8".to_string()));
+        assert_eq!(repl.prompt(), "> ");
+
+        assert_eq!(repl.feed(":type x / 2"), Feed::Output("float".to_string()));
+        assert_eq!(repl.feed(":js let y = x;"), Feed::Output("let y = x;".to_string()));
+        assert_eq!(repl.feed(":ast -x"), Feed::Output("{\n    (- (var x))\n}".to_string()));
+        assert_eq!(repl.feed(":tokens x"), Feed::Output("0:0 IDENTIFIER 'x'\n0:1 EOF ''".to_string()));
+
+        assert_eq!(repl.feed("1 +"), Feed::Incomplete);
+        assert!(matches!(repl.feed(""), Feed::Error(_)));
+        assert!(matches!(repl.feed("y"), Feed::Error(_)));
+        assert!(matches!(repl.feed("let z: int = \"a\";"), Feed::Error(_)));
+        assert!(matches!(repl.feed("'"), Feed::Error(_)));
+        assert_eq!(repl.feed(":quit"), Feed::Exit);
+    }
+
+    #[test]
+    fn test_repl_failed_entries() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.feed("let x = 1;"), Feed::Output(String::new()));
+        assert_eq!(repl.feed(":js let y = x; let y = 2; y"), Feed::Output("let y = x;\nlet y$1 = 2;\ny$1;".to_string()));
+
+        // Neither checked nor failed declarations are visible to later entries.
+        assert!(matches!(repl.feed("let z: int = \"a\";"), Feed::Error(_)));
+        assert_eq!(repl.feed(":type let w = 1.5;"), Feed::Output("float".to_string()));
+        assert!(matches!(repl.feed(":type z"), Feed::Error(error) if error.contains("Undefined variable 'z'")));
+        assert!(matches!(repl.feed(":type w"), Feed::Error(error) if error.contains("Undefined variable 'w'")));
+        assert_eq!(repl.feed(":type x"), Feed::Output("int".to_string()));
+
+        // Entry fails as a whole, so `a` is not declared, instead of having a value without a type.
+        assert!(matches!(repl.feed("let a = 1; b"), Feed::Error(error) if error.contains("Undefined variable 'b'")));
+        assert!(matches!(repl.feed("a + 1"), Feed::Error(error) if error.contains("Undefined variable 'a'")));
+        assert_eq!(repl.feed("let a = 2; a + x"), Feed::Output("3".to_string()));
+        assert_eq!(repl.feed("a + 1"), Feed::Output("3".to_string()));
+        assert_eq!(repl.feed(":type a"), Feed::Output("int".to_string()));
+    }
+}
@@ -1,6 +1,6 @@
-use std::{any::Any, rc::Rc};
+use std::{any::Any, fmt::Display, rc::Rc};
 
-use crate::util::HastyError;
+use crate::util::{diagnostic::Diagnostic, unified_error, HastyError};
 
 /// Represents type of a token.
 #[allow(non_camel_case_types)]
@@ -24,6 +24,9 @@ pub enum TokenType {
     FN, IF, ELSE, TRUE, FALSE, WHILE, FOR, RETURN, SELF,
     VAR, NIL, GUARD, PUB, IMPORT, FROM, AS, CONST, LET,
 
+    // Trivia, only produced by `Scanner::scan_with_comments`.
+    COMMENT,
+
     EOF
 }
 
@@ -42,6 +45,13 @@ pub struct Token {
     pub data: Option<Rc<dyn Any>>,
 }
 
+impl Display for Token {
+    /// Shows position, type and lexeme of a token.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{} {:?} '{}'", self.line, self.start, self.token_type, self.lexeme)
+    }
+}
+
 impl Token {
     /// Creates new token.
     pub fn new(token_type: TokenType, lexeme: String, line: usize, start: usize) -> Self {
@@ -61,32 +71,55 @@ impl Token {
     }
 }
 
-/// Possible errors that can occur during parsing.
+/// Possible errors that can occur during scanning.
 #[derive(Debug)]
-pub enum ScannerError {
+pub enum ScannerErrorTy {
     /// Found character that does not match any rule.
     UnexpectedCharacter,
     /// String was opened but not closed.
     UnterminatedString,
 }
 
+#[derive(Debug)]
+pub struct ScannerError {
+    pub ty: ScannerErrorTy,
+    /// Unexpected character, or the whole unterminated string.
+    pub token: Token,
+}
+
 impl HastyError for ScannerError {
-    fn as_hasty_error_string(&self) -> String {
-        return "TODO".to_string();
+    fn as_hasty_error_string(&self, source: &str) -> String {
+        unified_error("SCANNER", &self.get_error_description(), source, &self.token)
     }
 
     fn get_error_description(&self) -> String {
-        match self {
-            ScannerError::UnexpectedCharacter { .. } => "Unexpected character.",
-            ScannerError::UnterminatedString => "Unterminated string.",
-        }.to_string()
+        match self.ty {
+            ScannerErrorTy::UnexpectedCharacter => format!("Unexpected character '{}'.", self.token.lexeme),
+            ScannerErrorTy::UnterminatedString => "Unterminated string.".to_string(),
+        }
+    }
+
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+
+    fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(self.get_error_description());
+        match self.ty {
+            ScannerErrorTy::UnexpectedCharacter => diagnostic.with_code("E0001")
+                .with_primary(&self.token, "not valid in hasty"),
+            ScannerErrorTy::UnterminatedString => diagnostic.with_code("E0002")
+                .with_primary(&self.token, "string is never closed")
+                .with_help("add '\"' at the end of the string"),
+        }
     }
 }
 
 /// Type responsible for scanning source code and producing tokens.
 pub struct Scanner<'a> {
-    source: &'a [u8],
+    source: &'a str,
     tokens: Vec<Token>,
+    comments: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
@@ -96,8 +129,9 @@ impl<'a> Scanner<'a> {
     /// Create new scanner from source code.
     pub fn new(source: &'a str) -> Self {
         Self {
-            source: source.as_bytes(),
+            source,
             tokens: Vec::new(),
+            comments: Vec::new(),
             start: 0,
             current: 0,
             line: 0,
@@ -111,58 +145,58 @@ impl<'a> Scanner<'a> {
 
     /// Get next token.
     fn advance(&mut self) -> char {
-        let current_char = self.source[self.current] as char;
-        self.current += 1;
+        let current_char = self.peek();
+        self.current += current_char.len_utf8();
         current_char
     }
 
     /// Check next token without consuming it.
     fn peek(&self) -> char {
-        if self.is_at_end() { return '\0' }
-        self.source[self.current] as char
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     /// Peek next character.
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() { return '\0'; }
-        self.source[self.current + 1] as char
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     /// Add new token.
     fn add_token(&mut self, token_type: TokenType) {
-        let lexeme = &self.source[self.start..self.current];
-        // This should never panic, as inserting non-utf8 characters into source code is not common.
-        let lexeme = String::from_utf8(Vec::from(lexeme)).unwrap();
+        let lexeme = self.source[self.start..self.current].to_string();
         self.tokens.push(Token::new(token_type, lexeme, self.line, self.start))
     }
 
     /// Add new token with data.
     fn add_token_with_data(&mut self, token_type: TokenType, data: impl Any) {
-        let lexeme = &self.source[self.start..self.current];
-        // This should never panic, as inserting non-utf8 characters into source code is not common.
-        let lexeme = String::from_utf8(Vec::from(lexeme)).unwrap();
+        let lexeme = self.source[self.start..self.current].to_string();
         self.tokens.push(Token::new(token_type, lexeme, self.line, self.start).with_data(data))
     }
 
     /// Scans source code to produce tokens.
-    pub fn scan(mut self) -> Result<Vec<Token>, ScannerError> {
+    pub fn scan(self) -> Result<Vec<Token>, ScannerError> {
+        self.scan_with_comments().map(|(tokens, _)| tokens)
+    }
+
+    /// Scans source code to produce tokens, also returning comments which are
+    /// otherwise skipped. Comments are `COMMENT` tokens, in order of appearance.
+    pub fn scan_with_comments(mut self) -> Result<(Vec<Token>, Vec<Token>), ScannerError> {
         while !self.is_at_end() {
             // Begin new lexeme.
             self.start = self.current;
             self.scan_token()?;
         }
 
-        self.tokens.push(Token::new(TokenType::EOF, "".to_string(), self.line, 0));
-        Ok(self.tokens)
+        self.tokens.push(Token::new(TokenType::EOF, "".to_string(), self.line, self.source.len()));
+        Ok((self.tokens, self.comments))
     }
 
     /// Tries to match character if possible, consuming it if matches.
     fn try_match(&mut self, expected: char) -> bool {
         if self.is_at_end() { return false }
-        if self.source[self.current] as char != expected { return false }
+        if self.peek() != expected { return false }
 
         self.current += 1;
-        return true;
+        true
     }
 
     /// Scan one token.
@@ -201,14 +235,15 @@ impl<'a> Scanner<'a> {
             // More complicated tokens.
             '/' => {
                 if self.try_match('/') {
-                    // Ignore comment.
+                    // Comment is kept aside, so it does not reach the parser.
                     while self.peek() != '\n' && !self.is_at_end() { self.advance(); }
+                    let lexeme = self.source[self.start..self.current].to_string();
+                    self.comments.push(Token::new(TokenType::COMMENT, lexeme, self.line, self.start));
                 } else {
                     self.add_token(TokenType::SLASH)
                 }
             }
             '"' => { self.string()?; },
-            '\'' => { self.character()?; },
             '0'..='9' => { self.number()?; },
             '_' => {
                 if self.peek().is_whitespace() {
@@ -218,6 +253,7 @@ impl<'a> Scanner<'a> {
                 }
             },
             '$' | 'A'..='z' => { self.identifier()?; },
+            c if c.is_alphabetic() => { self.identifier()?; },
 
             // Useless characters.
             ' ' | '\r' | '\t' => { /* ignore */ },
@@ -234,48 +270,48 @@ impl<'a> Scanner<'a> {
 
     /// Return unexpected char error
     fn err_unexpected_char(&self) -> Result<(), ScannerError> {
-        Err(ScannerError::UnexpectedCharacter)
+        let lexeme = self.source[self.start..self.current].to_string();
+        let token = Token::new(TokenType::EOF, lexeme, self.line, self.start);
+        Err(ScannerError { ty: ScannerErrorTy::UnexpectedCharacter, token })
     }
 
     /// Produce token for string.
     fn string(&mut self) -> Result<(), ScannerError> {
         // TODO: Add support for escape characters in strings.
+        let line = self.line;
         while self.peek() != '"' && !self.is_at_end() {
             if self.peek() == '\n' { self.line += 1 };
             self.advance();
         }
 
         if self.is_at_end() {
-            Err(ScannerError::UnterminatedString)?;
+            let lexeme = self.source[self.start..].to_string();
+            let token = Token::new(TokenType::STRING, lexeme, line, self.start);
+            Err(ScannerError { ty: ScannerErrorTy::UnterminatedString, token })?;
         }
 
         // Match closing ".
         self.advance();
 
         // Produce token.
-        let value = String::from_utf8(self.source[self.start + 1..self.current - 1].to_owned());
-        self.add_token_with_data(TokenType::STRING, value.unwrap());
+        let value = self.source[self.start + 1..self.current - 1].to_string();
+        self.add_token_with_data(TokenType::STRING, value);
         Ok(())
     }
 
-    /// Produce token for character literal.
-    fn character(&mut self) -> Result<(), ScannerError> {
-        unimplemented!()
-    }
-
     /// Produce token for number literal.
     fn number(&mut self) -> Result<(), ScannerError> {
-        while self.peek().is_digit(10) { self.advance(); }
+        while self.peek().is_ascii_digit() { self.advance(); }
         let mut is_floating = false;
 
         // Look for fractional part.
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             is_floating = true;
 
             // Consume ".".
             self.advance();
 
-            while self.peek().is_digit(10) { self.advance(); }
+            while self.peek().is_ascii_digit() { self.advance(); }
         }
 
         self.add_token(if is_floating { TokenType::FLOATING } else { TokenType::INTEGER });
@@ -284,12 +320,9 @@ impl<'a> Scanner<'a> {
 
     /// Produce token for identifiers
     fn identifier(&mut self) -> Result<(), ScannerError> {
-        while self.peek().is_alphanumeric() { self.advance(); }
+        while self.peek().is_alphanumeric() || self.peek() == '_' { self.advance(); }
 
-        let text = String::from_utf8(
-            Vec::from(&self.source[self.start..self.current])
-        ).unwrap();
-        let text = text.as_str();
+        let text = &self.source[self.start..self.current];
 
         // Match keywords.
         self.add_token(
@@ -0,0 +1,111 @@
+use crate::{
+    backend::interpreter::{Interpreter, RuntimeError},
+    parser::ast::{ASTNode, BoxedASTNode},
+};
+
+/// Result of a single test.
+pub struct TestResult {
+    pub name: String,
+    /// Error of the first failed assertion, or any other runtime error.
+    pub outcome: Result<(), RuntimeError>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
+/// Results of all tests in a module.
+pub struct TestReport {
+    pub results: Vec<TestResult>,
+    /// Number of tests skipped by the filter.
+    pub filtered: usize,
+}
+
+/// Runs `test fn` declarations of a module with the interpreter.
+/// Every test runs after a fresh run of the whole module, so tests do not affect each other.
+pub struct TestRunner {
+    filter: Option<String>,
+}
+
+impl Default for TestRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestRunner {
+    pub fn new() -> Self {
+        Self { filter: None }
+    }
+
+    /// Only runs tests whose name contains `filter`.
+    pub fn with_filter(filter: impl Into<String>) -> Self {
+        Self { filter: Some(filter.into()) }
+    }
+
+    /// Test declarations of a module, in order of appearance.
+    pub fn discover(ast: &[BoxedASTNode]) -> Vec<BoxedASTNode> {
+        ast.iter().filter(|node| node.borrow().is_test()).cloned().collect()
+    }
+
+    /// Runs every test of an analyzed module that matches the filter.
+    pub fn run(&self, ast: &[BoxedASTNode]) -> TestReport {
+        let mut report = TestReport { results: Vec::new(), filtered: 0 };
+
+        for test in Self::discover(ast) {
+            let ASTNode::Test { ref name, .. } = **test.borrow() else { continue };
+            if self.filter.as_ref().is_some_and(|filter| !name.lexeme.contains(filter.as_str())) {
+                report.filtered += 1;
+                continue;
+            }
+
+            let mut interpreter = Interpreter::new();
+            let outcome = interpreter.interpret(ast).and_then(|_| interpreter.run_test(&test));
+            report.results.push(TestResult { name: name.lexeme.clone(), outcome });
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::interpreter::RuntimeErrorTy, util::HastyError};
+
+    use super::*;
+
+    #[track_caller]
+    fn run(code: &str, runner: TestRunner) -> TestReport {
+        let analysis = crate::analyze(code).unwrap();
+        runner.run(&analysis.ast)
+    }
+
+    #[test]
+    fn test_run_tests() {
+        let code = "\
+pub let total = 2 + 2;
+test fn addition() {
+    let expected = 4;
+    assert(total == expected)
+}
+test fn strings() { assert(\"a\" + \"b\" == \"ab\", \"concatenation\") }
+test fn broken() {
+    let x = total;
+    assert(x > 10, \"x is too small\")
+}
+test fn empty() {}
+";
+        let report = run(code, TestRunner::new());
+        let passed: Vec<_> = report.results.iter().map(|result| (result.name.as_str(), result.passed())).collect();
+        assert_eq!(passed, [("addition", true), ("strings", true), ("broken", false), ("empty", true)]);
+
+        let error = report.results[2].outcome.as_ref().unwrap_err();
+        assert!(matches!(error.ty, RuntimeErrorTy::AssertionFailed { .. }));
+        assert_eq!(error.get_error_description(), "Assertion failed: x is too small");
+        assert_eq!(error.token.line, 8);
+
+        let report = run(code, TestRunner::with_filter("str"));
+        assert_eq!((report.results.len(), report.filtered), (1, 3));
+    }
+}
@@ -1,51 +1,106 @@
 use colored::Colorize;
 
+use crate::scanner::Token;
+
+use self::{diagnostic::Diagnostic, source::{display_width, SourceLines}};
+
+pub mod diagnostic;
+pub mod json;
 pub mod metacontainer;
 pub mod scope;
+pub mod source;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod trace;
 
 /// Trait for displaying hasty lang errors.
 pub trait HastyError {
-    fn as_hasty_error_string(&self) -> String;
+    /// Error in the unified form, see `unified_error`.
+    fn as_hasty_error_string(&self, source: &str) -> String;
     fn get_error_description(&self) -> String;
+
+    /// Token at which the error occurred, if it is known.
+    fn token(&self) -> Option<&Token> {
+        None
+    }
+
+    /// Structured form of the error, pointing at its token.
+    fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(self.get_error_description());
+        match self.token() {
+            Some(token) => diagnostic.with_primary(token, ""),
+            None => diagnostic,
+        }
+    }
 }
 
+/// Lines shown before and after the line with an error.
+const CONTEXT_LINES: usize = 1;
+
 /// Shows error in a unified form:
-/// \[<pipeline_part>] Error \<line>.\<char>: \<error>
+/// \[<pipeline_part>] Error \<line>:\<column>: \<error>
 ///
 /// <line_preview>
 ///
 /// <error_pointer>
-pub fn unified_error(
-    pipeline_part: &str, error: &str,
-    line: usize, start: usize,
-    lexeme: &str, line_str: &str,
-) -> String {
-    let mut result = String::new();
+///
+/// Line preview is taken from `source`, with line numbers and surrounding lines.
+/// It is left out if `source` is empty.
+pub fn unified_error(pipeline_part: &str, error: &str, source: &str, token: &Token) -> String {
+    let lines = SourceLines::new(source);
+    let (line, column) = lines.position(token.start);
 
     // Error info.
-    result.push_str(
-        &format!("[{}] {} {}{}{}{} {}\n",
-            pipeline_part.yellow(),
-            "Error".red(),
-            line.to_string().red(),
-            ".".red(),
-            start.to_string().red(),
-            ":".red(),
-            error.red()
-        )
+    let mut result = format!("[{}] {} {} {}\n",
+        pipeline_part.yellow(),
+        "Error".red(),
+        format!("{}:{}:", line + 1, column + 1).red(),
+        error.red()
     );
 
-    if line_str == "" { return result; }
+    if source.is_empty() { return result; }
+
+    let last = (line + CONTEXT_LINES).min(lines.line_count() - 1);
+    let width = (last + 1).to_string().len();
 
-    // Line preview.
-    result.push_str(line_str.trim());
-    result.push('\n');
+    for number in line.saturating_sub(CONTEXT_LINES)..=last {
+        // Line preview.
+        let text = lines.display_text(number);
+        let text = if number == line { text.normal() } else { text.dimmed() };
+        result.push_str(&format!("{} {} {}\n", format!("{:>width$}", number + 1, width = width).blue(), "|".blue(), text));
 
-    // Error indicator.
-    let indicator = "^".to_string().repeat(lexeme.len()).yellow();
-    let whitespaces = " ".to_string().repeat(start);
+        if number != line { continue; }
 
-    result.push_str(&format!("{}{} {}", whitespaces, indicator, "Here".to_string().yellow()));
+        // Error indicator, only up to the end of the line for tokens spanning multiple lines.
+        let start = lines.display_column(token.start);
+        let end = token.start + token.lexeme.len();
+        let end = if lines.line(end) == line { lines.display_column(end) } else { display_width(&lines.display_text(line)) };
+        let indicator = "^".repeat(end.saturating_sub(start).max(1)).yellow();
+        result.push_str(&format!("{} {} {}{} {}\n", " ".repeat(width), "|".blue(), " ".repeat(start), indicator, "Here".yellow()));
+    }
 
     result
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scanner::TokenType;
+
+    use super::*;
+
+    #[test]
+    fn test_unified_error() {
+        colored::control::set_override(false);
+
+        let source = "let a = 1;\n\tlet 変数 = b;\nlet c = 3;\nlet d = 4;";
+        let token = Token::new(TokenType::IDENTIFIER, "b".to_string(), 1, 25);
+        assert_eq!(unified_error("RESOLVER", "Undefined variable 'b'.", source, &token), "\
+[RESOLVER] Error 2:11: Undefined variable 'b'.
+1 | let a = 1;
+2 |     let 変数 = b;
+  |                ^ Here
+3 | let c = 3;
+");
+        assert_eq!(unified_error("PARSER", "Expected expression.", "", &token), "[PARSER] Error 1:1: Expected expression.\n");
+    }
+}
@@ -0,0 +1,364 @@
+//! Structured description of errors and warnings.
+//!
+//! Codes are grouped by the stage that reports them:
+//! `E00xx` scanner, `E01xx` parser, `E02xx` resolver, `E03xx` type checker,
+//! `E04xx` constant folding, `E05xx` code generation, `E06xx` interpreter,
+//! `W00xx` warnings and `W01xx` lints.
+
+use std::fmt::Display;
+
+use colored::Colorize;
+
+use crate::scanner::Token;
+
+use super::{json::Json, source::{display_width, SourceLines, TAB_WIDTH}};
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        })
+    }
+}
+
+/// Range of bytes in source code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+impl From<&Token> for Span {
+    fn from(token: &Token) -> Self {
+        Self::new(token.start, token.start + token.lexeme.len())
+    }
+}
+
+/// Span with a message shown under it.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    /// Primary label points at the cause, secondary labels add context.
+    pub primary: bool,
+}
+
+/// Replacement of source code that fixes the problem.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// Error, warning or note with everything needed to explain it.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// Stable identifier, like `E0201`.
+    pub code: Option<&'static str>,
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            code: None,
+            severity,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_primary(mut self, span: impl Into<Span>, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span: span.into(), message: message.into(), primary: true });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: impl Into<Span>, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span: span.into(), message: message.into(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    pub fn with_suggestion(mut self, message: impl Into<String>, span: impl Into<Span>, replacement: impl Into<String>) -> Self {
+        self.suggestions.push(Suggestion { message: message.into(), span: span.into(), replacement: replacement.into() });
+        self
+    }
+
+    /// Span of the first primary label.
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels.iter().find(|label| label.primary).map(|label| label.span)
+    }
+
+    /// Renders diagnostic with the lines of `source` its labels point at.
+    pub fn render(&self, source: &str) -> String {
+        let lines = SourceLines::new(source);
+        let mut result = String::new();
+
+        let severity = self.severity.to_string();
+        let header = match self.code {
+            Some(code) => format!("{}[{}]", severity, code),
+            None => severity,
+        };
+        result.push_str(&format!("{}{} {}\n", self.paint(&header).bold(), ":".bold(), self.message.bold()));
+
+        // Width of the widest line number, so that gutters are aligned.
+        let last_line = self.labels.iter().chain(self.suggestion_labels().iter())
+            .map(|label| lines.line(label.span.end.max(label.span.start)) + 1)
+            .max();
+        let width = last_line.map_or(0, |line| line.to_string().len());
+        let gutter = " ".repeat(width);
+
+        if let Some(span) = self.primary_span().or(self.labels.first().map(|label| label.span)) {
+            let (line, column) = lines.position(span.start);
+            result.push_str(&format!("{}{} {}:{}\n", gutter, "-->".blue().bold(), line + 1, column + 1));
+        }
+
+        if !self.labels.is_empty() {
+            result.push_str(&format!("{} {}\n", gutter, "|".blue().bold()));
+            result.push_str(&self.render_labels(&lines, &self.labels, width));
+        }
+
+        for note in &self.notes {
+            result.push_str(&format!("{} {} {}: {}\n", gutter, "=".blue().bold(), "note".bold(), note));
+        }
+        for help in &self.help {
+            result.push_str(&format!("{} {} {}: {}\n", gutter, "=".blue().bold(), "help".bold(), help));
+        }
+
+        for suggestion in &self.suggestions {
+            result.push_str(&format!("{}: {}\n", "help".bold(), suggestion.message));
+            result.push_str(&self.render_suggestion(&lines, suggestion, width));
+        }
+
+        result
+    }
+
+    /// Source lines touched by labels, each followed by underlines of the labels on it.
+    fn render_labels(&self, lines: &SourceLines, labels: &[Label], width: usize) -> String {
+        let mut numbers: Vec<usize> = labels.iter()
+            .flat_map(|label| lines.line(label.span.start)..=lines.line(label.span.end.max(label.span.start)))
+            .collect();
+        numbers.sort();
+        numbers.dedup();
+
+        // Multi-line labels are drawn in a margin left of the source.
+        let multiline: Vec<&Label> = labels.iter()
+            .filter(|label| lines.line(label.span.start) != lines.line(label.span.end))
+            .collect();
+        let margin = if multiline.is_empty() { "" } else { "  " };
+
+        let mut result = String::new();
+        let mut previous = None;
+        for number in numbers {
+            if previous.is_some_and(|previous| number > previous + 1) {
+                result.push_str(&format!("{}\n", "...".blue().bold()));
+            }
+            previous = Some(number);
+
+            let inside = multiline.iter().any(|label| {
+                lines.line(label.span.start) < number && number <= lines.line(label.span.end)
+            });
+            let line_margin = if inside { format!("{} ", self.paint("|")) } else { margin.to_string() };
+            result.push_str(&format!(
+                "{} {} {}{}\n",
+                format!("{:>width$}", number + 1, width = width).blue().bold(),
+                "|".blue().bold(), line_margin, lines.display_text(number)
+            ));
+
+            for label in labels.iter() {
+                let (start_line, start) = (lines.line(label.span.start), lines.display_column(label.span.start));
+                let (end_line, end) = (lines.line(label.span.end), lines.display_column(label.span.end));
+
+                let underline = if start_line == end_line && start_line == number {
+                    let marker = if label.primary { "^" } else { "-" };
+                    format!("{}{}{}", margin, " ".repeat(start), marker.repeat(end.saturating_sub(start).max(1)))
+                } else if start_line == number {
+                    format!(" {}^", "_".repeat(start + 1))
+                } else if end_line == number {
+                    format!("|{}^", "_".repeat(end.max(1)))
+                } else {
+                    continue;
+                };
+
+                let message = if label.message.is_empty() || (start_line != end_line && start_line == number) {
+                    String::new()
+                } else {
+                    format!(" {}", label.message)
+                };
+                let text = format!("{}{}", underline, message);
+                let text = if label.primary { self.paint(&text) } else { text.blue().bold() };
+                result.push_str(&format!("{} {} {}\n", " ".repeat(width), "|".blue().bold(), text));
+            }
+        }
+        result
+    }
+
+    /// Line of the suggestion with the replacement applied.
+    fn render_suggestion(&self, lines: &SourceLines, suggestion: &Suggestion, width: usize) -> String {
+        let line = lines.line(suggestion.span.start);
+        let line_start = lines.start(line);
+        let text = lines.text(line);
+
+        let tab = " ".repeat(TAB_WIDTH);
+        let before = text[..suggestion.span.start - line_start].replace('\t', &tab);
+        let after = text.get(suggestion.span.end - line_start..).unwrap_or("").replace('\t', &tab);
+        let column = display_width(&before);
+        let added = display_width(&suggestion.replacement).max(1);
+
+        format!(
+            "{gutter} {bar}\n{number} {bar} {}{}{}\n{gutter} {bar} {}{}\n",
+            before, suggestion.replacement, after,
+            " ".repeat(column), "+".repeat(added).green(),
+            gutter = " ".repeat(width), bar = "|".blue().bold(),
+            number = format!("{:>width$}", line + 1, width = width).blue().bold(),
+        )
+    }
+
+    /// Machine readable form of the diagnostic. Spans have byte offsets, and lines
+    /// and columns starting at 1, with columns counted in characters.
+    /// `file` is `null` for source which was not read from a file.
+    pub fn to_json(&self, source: &str, file: Option<&str>) -> Json {
+        let lines = SourceLines::new(source);
+        let span = |span: Span| {
+            let point = |offset: usize| {
+                let (line, column) = lines.position(offset);
+                Json::object([("offset", offset.into()), ("line", (line + 1).into()), ("column", (column + 1).into())])
+            };
+            Json::object([("start", point(span.start)), ("end", point(span.end))])
+        };
+
+        let labels = self.labels.iter().map(|label| Json::object([
+            ("primary", label.primary.into()),
+            ("message", label.message.as_str().into()),
+            ("span", span(label.span)),
+        ])).collect::<Vec<_>>();
+        let suggestions = self.suggestions.iter().map(|suggestion| Json::object([
+            ("message", suggestion.message.as_str().into()),
+            ("replacement", suggestion.replacement.as_str().into()),
+            ("span", span(suggestion.span)),
+        ])).collect::<Vec<_>>();
+        let strings = |values: &[String]| values.iter().map(|value| value.as_str().into()).collect::<Vec<Json>>();
+
+        Json::object([
+            ("code", self.code.into()),
+            ("severity", self.severity.to_string().into()),
+            ("message", self.message.as_str().into()),
+            ("file", file.into()),
+            ("labels", labels.into()),
+            ("notes", strings(&self.notes).into()),
+            ("help", strings(&self.help).into()),
+            ("suggestions", suggestions.into()),
+        ])
+    }
+
+    /// Suggestions as labels, used to find the widest line number.
+    fn suggestion_labels(&self) -> Vec<Label> {
+        self.suggestions.iter()
+            .map(|suggestion| Label { span: suggestion.span, message: String::new(), primary: false })
+            .collect()
+    }
+
+    /// Colors text according to the severity.
+    fn paint(&self, text: &str) -> colored::ColoredString {
+        match self.severity {
+            Severity::Error => text.red().bold(),
+            Severity::Warning => text.yellow().bold(),
+            Severity::Note => text.green().bold(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        colored::control::set_override(false);
+
+        let source = "let x: number = 1;\nlet s = \"abc\ndef";
+        let diagnostic = Diagnostic::error("Unknown type 'number'.")
+            .with_code("E0301")
+            .with_primary(Span::new(7, 13), "not a type")
+            .with_secondary(Span::new(4, 5), "declared here")
+            .with_note("types are int, float, string, bool and nil")
+            .with_suggestion("use a known type", Span::new(7, 13), "float");
+        assert_eq!(diagnostic.render(source), "\
+error[E0301]: Unknown type 'number'.
+ --> 1:8
+  |
+1 | let x: number = 1;
+  |        ^^^^^^ not a type
+  |     - declared here
+  = note: types are int, float, string, bool and nil
+help: use a known type
+  |
+1 | let x: float = 1;
+  |        +++++
+");
+
+        assert_eq!(
+            diagnostic.to_json(source, Some("main.hasty")).path(&["suggestions"]).unwrap().to_string(),
+            r#"[{"message":"use a known type","replacement":"float","span":{"start":{"offset":7,"line":1,"column":8},"end":{"offset":13,"line":1,"column":14}}}]"#
+        );
+        assert_eq!(diagnostic.to_json(source, None).get("file"), Some(&Json::Null));
+
+        let diagnostic = Diagnostic::error("Unterminated string.").with_primary(Span::new(27, 35), "string starts here");
+        assert_eq!(diagnostic.render(source), "\
+error: Unterminated string.
+ --> 2:9
+  |
+2 |   let s = \"abc
+  |  _________^
+3 | | def
+  | |___^ string starts here
+");
+    }
+}
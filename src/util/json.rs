@@ -0,0 +1,320 @@
+use std::fmt::Display;
+
+/// JSON value, as used by the language server and machine readable output.
+/// Object fields keep their order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct JsonError {
+    pub message: &'static str,
+    /// Byte offset at which parsing failed.
+    pub offset: usize,
+}
+
+impl Json {
+    pub fn parse(source: &str) -> Result<Json, JsonError> {
+        let mut parser = JsonParser { source: source.as_bytes(), current: 0 };
+        let value = parser.value()?;
+        parser.whitespace();
+        if parser.current < source.len() {
+            return Err(parser.error("Unexpected trailing characters."));
+        }
+        Ok(value)
+    }
+
+    /// Creates object from field names and values.
+    pub fn object<const N: usize>(fields: [(&str, Json); N]) -> Json {
+        Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    /// Field of an object, `None` for missing fields and other values.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Field nested in objects, e.g. `["textDocument", "uri"]`.
+    pub fn path(&self, keys: &[&str]) -> Option<&Json> {
+        keys.iter().try_fold(self, |value, key| value.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64().filter(|value| *value >= 0.0 && value.fract() == 0.0).map(|value| value as usize)
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self { Json::String(value.to_string()) }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self { Json::String(value) }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self { Json::Bool(value) }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self { Json::Number(value as f64) }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(values: Vec<Json>) -> Self { Json::Array(values) }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self { value.map_or(Json::Null, Into::into) }
+}
+
+impl Display for Json {
+    /// Compact JSON text.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            // JSON has no representation for NaN and infinities.
+            Json::Number(value) if !value.is_finite() => write!(f, "null"),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write!(f, "{}", quote(value)),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}:{}", quote(key), value)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+/// Quotes and escapes string for use in JSON.
+pub fn quote(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+struct JsonParser<'a> {
+    source: &'a [u8],
+    current: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.whitespace();
+        match self.peek() {
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'[') => {
+                self.current += 1;
+                let mut values = Vec::new();
+                if !self.try_match(b']') {
+                    loop {
+                        values.push(self.value()?);
+                        if self.try_match(b']') { break; }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Array(values))
+            },
+            Some(b'{') => {
+                self.current += 1;
+                let mut fields = Vec::new();
+                if !self.try_match(b'}') {
+                    loop {
+                        self.whitespace();
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        fields.push((key, self.value()?));
+                        if self.try_match(b'}') { break; }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Object(fields))
+            },
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => Err(self.error("Expected value.")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, JsonError> {
+        if self.source[self.current..].starts_with(keyword.as_bytes()) {
+            self.current += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error("Expected value."))
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.current;
+        while matches!(self.peek(), Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.current += 1;
+        }
+        std::str::from_utf8(&self.source[start..self.current]).ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or(JsonError { message: "Invalid number.", offset: start })
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut result = Vec::new();
+        loop {
+            match self.advance() {
+                None => return Err(self.error("Unterminated string.")),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    let escaped = match self.advance() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.unicode_escape()?,
+                        _ => return Err(self.error("Invalid escape.")),
+                    };
+                    let mut buffer = [0; 4];
+                    result.extend_from_slice(escaped.encode_utf8(&mut buffer).as_bytes());
+                },
+                Some(byte) => result.push(byte),
+            }
+        }
+        String::from_utf8(result).map_err(|_| self.error("Invalid UTF-8."))
+    }
+
+    /// Reads `XXXX` after `\u`, including the second half of a surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.hex()?;
+        if (0xd800..0xdc00).contains(&high) {
+            if !(self.try_match(b'\\') && self.try_match(b'u')) {
+                return Err(self.error("Unpaired surrogate."));
+            }
+            let low = self.hex()?;
+            let code = 0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+            return char::from_u32(code).ok_or(self.error("Invalid escape."));
+        }
+        char::from_u32(high).ok_or(self.error("Invalid escape."))
+    }
+
+    fn hex(&mut self) -> Result<u32, JsonError> {
+        let digits = self.source.get(self.current..self.current + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or(self.error("Invalid escape."))?;
+        self.current += 4;
+        Ok(digits)
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.current += 1;
+        }
+    }
+
+    fn try_match(&mut self, expected: u8) -> bool {
+        self.whitespace();
+        if self.peek() == Some(expected) {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), JsonError> {
+        if self.try_match(expected) { Ok(()) } else { Err(self.error("Unexpected character.")) }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.get(self.current).copied()
+    }
+
+    fn advance(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.current += 1;
+        Some(byte)
+    }
+
+    fn error(&self, message: &'static str) -> JsonError {
+        JsonError { message, offset: self.current }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let source = r#"{"id": 1, "params": {"text": "a\n\"b\" é😀", "list": [true, null, -1.5e2]}}"#;
+        let json = Json::parse(source).unwrap();
+        assert_eq!(json.get("id").and_then(Json::as_usize), Some(1));
+        assert_eq!(json.path(&["params", "text"]).and_then(Json::as_str), Some("a\n\"b\" é😀"));
+        assert_eq!(
+            json.to_string(),
+            r#"{"id":1,"params":{"text":"a\n\"b\" é😀","list":[true,null,-150]}}"#
+        );
+
+        assert_eq!(Json::parse("[1,]").unwrap_err().offset, 3);
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("\"abc").is_err());
+    }
+}
@@ -14,6 +14,12 @@ impl Debug for MetaContainer {
     }
 }
 
+impl Default for MetaContainer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MetaContainer {
     pub fn new() -> Self {
         Self {
@@ -21,12 +27,12 @@ impl MetaContainer {
         }
     }
 
-    pub fn get<'a, T: 'static>(&'a self) -> Option<Rc<T>> {
+    pub fn get<T: 'static>(&self) -> Option<Rc<T>> {
         self.meta.borrow().get(&TypeId::of::<T>())
-            .and_then(|x| x.downcast_ref::<Rc<T>>().and_then(|x| Some(Rc::clone(x))))
+            .and_then(|x| x.downcast_ref::<Rc<T>>().map(Rc::clone))
     }
 
-    pub fn insert<T: 'static>(&mut self, value: T) {
+    pub fn insert<T: 'static>(&self, value: T) {
         self.meta.borrow_mut().insert(TypeId::of::<T>(), Box::new(Rc::new(value)));
     }
 }
@@ -37,7 +43,7 @@ mod tests {
 
     #[test]
     fn test_metacontainer() {
-        let mut meta = MetaContainer::new();
+        let meta = MetaContainer::new();
         meta.insert(1);
         assert_eq!(*meta.get::<i32>().unwrap(), 1);
     }
@@ -9,8 +9,16 @@ struct GenericScope<T> {
 }
 
 /// Simple scope implementation. Used for storing variables and metadata.
+/// Clones share the same variables.
+#[derive(Clone)]
 pub struct Scope<T>(Rc<GenericScope<T>>);
 
+impl<T: Clone> Default for Scope<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Clone> Scope<T> {
     pub fn new() -> Self {
         Self(Rc::new(GenericScope {
@@ -48,7 +56,7 @@ impl<T: Clone> Scope<T> {
         let mut scope = Some(&self.0);
 
         while let Some(current) = scope {
-            if let Some(_) = current.variables.borrow().get(name) {
+            if current.variables.borrow().get(name).is_some() {
                 current.variables.borrow_mut().insert(name.to_string(), value);
                 return;
             }
@@ -59,12 +67,34 @@ impl<T: Clone> Scope<T> {
         self.0.variables.borrow_mut().insert(name.to_string(), value);
     }
 
-    pub fn parent(&self) -> Option<Self> {
+    /// Names visible from this scope, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut scope = Some(&self.0);
+
+        while let Some(current) = scope {
+            for name in current.variables.borrow().keys() {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+
+            scope = current.parent.as_ref();
+        }
+
+        names.sort();
+        names
+    }
+
+    /// Moves variables of this scope into its parent.
+    pub fn commit(&self) {
         if let Some(parent) = &self.0.parent {
-            Some(Self(Rc::clone(parent)))
-        } else {
-            None
+            parent.variables.borrow_mut().extend(self.0.variables.borrow_mut().drain());
         }
     }
 
+    pub fn parent(&self) -> Option<Self> {
+        self.0.parent.as_ref().map(|parent| Self(Rc::clone(parent)))
+    }
+
 }
\ No newline at end of file
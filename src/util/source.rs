@@ -0,0 +1,79 @@
+/// Number of columns a tab is expanded to when source is shown.
+pub const TAB_WIDTH: usize = 4;
+
+/// Source code split into lines, for showing parts of it in diagnostics.
+pub struct SourceLines<'a> {
+    source: &'a str,
+    /// Offset at which each line starts.
+    starts: Vec<usize>,
+}
+
+impl<'a> SourceLines<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { source, starts }
+    }
+
+    /// Number of lines, a trailing line break starts an empty line.
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Index of the line containing offset.
+    pub fn line(&self, offset: usize) -> usize {
+        self.starts.partition_point(|start| *start <= offset.min(self.source.len())) - 1
+    }
+
+    /// Offset at which the line starts.
+    pub fn start(&self, line: usize) -> usize {
+        self.starts[line]
+    }
+
+    /// Line and column of an offset, with column counted in characters.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = self.line(offset);
+        let column = self.source.get(self.starts[line]..offset).map_or(0, |text| text.chars().count());
+        (line, column)
+    }
+
+    /// Column at which character at offset is shown, after expanding tabs
+    /// and accounting for wide characters.
+    pub fn display_column(&self, offset: usize) -> usize {
+        let offset = offset.min(self.source.len());
+        let start = self.starts[self.line(offset)];
+        self.source.get(start..offset).map_or(0, display_width)
+    }
+
+    /// Text of the line, without the line break.
+    pub fn text(&self, line: usize) -> &'a str {
+        let end = self.starts.get(line + 1).map_or(self.source.len(), |next| next - 1);
+        self.source[self.starts[line]..end].trim_end_matches('\r')
+    }
+
+    /// Text of the line as it is shown, with tabs expanded to spaces.
+    pub fn display_text(&self, line: usize) -> String {
+        self.text(line).replace('\t', &" ".repeat(TAB_WIDTH))
+    }
+}
+
+/// Number of terminal columns text takes up.
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Number of terminal columns a character takes up. Combining characters take none,
+/// and east asian wide characters and emoji take two.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x09 => TAB_WIDTH,
+        0x0300..=0x036F | 0x200B..=0x200F | 0x20D0..=0x20FF | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF | 0xA000..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
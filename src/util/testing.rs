@@ -0,0 +1,50 @@
+//! Fixtures shared by tests of all modules.
+
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use crate::{
+    parser::{ast::BoxedASTNode, Parser},
+    passes::{resolver::Resolver, typecheck::TypeChecker, Pass},
+    scanner::Scanner,
+};
+
+/// Scans and parses code that has to be valid.
+#[track_caller]
+pub(crate) fn parse(code: &str) -> Vec<BoxedASTNode> {
+    let tokens = Scanner::new(code).scan().unwrap();
+    Parser::new(tokens).parse().unwrap()
+}
+
+/// Parses code and resolves its bindings.
+#[track_caller]
+pub(crate) fn resolve(code: &str) -> Vec<BoxedASTNode> {
+    let mut ast = parse(code);
+    Resolver::new().process(&mut ast).unwrap();
+    ast
+}
+
+/// Parses code, resolves its bindings and checks its types.
+#[track_caller]
+pub(crate) fn typecheck(code: &str) -> Vec<BoxedASTNode> {
+    let mut ast = resolve(code);
+    TypeChecker::new().process(&mut ast).unwrap();
+    ast
+}
+
+/// Writer that can be inspected after being moved, for example into a tracer.
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+}
@@ -0,0 +1,43 @@
+use std::{cell::RefCell, io::Write, rc::Rc, time::Duration};
+
+use crate::parser::ast::ASTNode;
+
+/// Writes structured trace of compilation into a separate sink.
+/// Every event is a single line of `key=value` pairs, so it can be easily filtered.
+/// Can be cheaply cloned to share one sink between compilation stages.
+#[derive(Clone)]
+pub struct Tracer {
+    sink: Rc<RefCell<Box<dyn Write>>>,
+}
+
+impl Tracer {
+    pub fn new(sink: impl Write + 'static) -> Self {
+        Self {
+            sink: Rc::new(RefCell::new(Box::new(sink))),
+        }
+    }
+
+    /// Tracer writing to stderr, so that it does not mix with compiled output.
+    pub fn stderr() -> Self {
+        Self::new(std::io::stderr())
+    }
+
+    /// Single node was processed by a pass.
+    pub fn node(&self, pass: &str, node: &ASTNode, elapsed: Duration) {
+        let token = node.position();
+        self.write(format!(
+            "pass={} node={} line={} start={} time={:?}",
+            pass, node.kind(), token.line, token.start, elapsed
+        ));
+    }
+
+    /// Whole pass has finished.
+    pub fn pass(&self, pass: &str, elapsed: Duration) {
+        self.write(format!("pass={} time={:?}", pass, elapsed));
+    }
+
+    fn write(&self, event: String) {
+        // Tracing should never make compilation fail.
+        let _ = writeln!(self.sink.borrow_mut(), "{}", event);
+    }
+}